@@ -0,0 +1,51 @@
+//! Compare building IDs with SipHash ([`labrat::hash::id_from_consistent_hash`]) against SHA-224 over JSON
+//! ([`labrat::id_from_serialised`]).  Run with `cargo bench --bench id_hash`.
+use labrat::{hash::id_from_consistent_hash, id_from_serialised, impl_consistent_hash};
+use serde::Serialize;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+#[derive(Serialize)]
+struct Params {
+    epsilon: f64,
+    cpus: u16,
+    seeds: Vec<u64>,
+    name: String,
+}
+
+impl_consistent_hash!(Params {
+    epsilon,
+    cpus,
+    seeds,
+    name
+});
+
+const ITERATIONS: u32 = 100_000;
+
+fn time(f: impl Fn() -> String) -> Duration {
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        black_box(f());
+    }
+    start.elapsed() / ITERATIONS
+}
+
+fn main() {
+    for n_seeds in [0, 10, 1000] {
+        let params = Params {
+            epsilon: 0.0001,
+            cpus: 4,
+            seeds: (0..n_seeds).collect(),
+            name: "benchmark".into(),
+        };
+        let sha = time(|| id_from_serialised(black_box(&params)));
+        let sip = time(|| id_from_consistent_hash(black_box(&params)));
+        println!(
+            "{:>5} seeds: SHA-224 over JSON {:>10?}, SipHash {:>10?} ({:.1}x)",
+            n_seeds,
+            sha,
+            sip,
+            sha.as_secs_f64() / sip.as_secs_f64()
+        );
+    }
+}
@@ -3,6 +3,7 @@
 use anyhow::Result;
 use labrat::*;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use std::time::Duration;
 
@@ -51,6 +52,12 @@ struct Params {
     /// Parameter cat
     #[clap(arg_enum, long, default_value_t=Penum::Foo)]
     cat: Penum,
+    /// Random seeds, eg `--seeds 1,2,3`
+    #[clap(long, value_delimiter = ',')]
+    seeds: Vec<u64>,
+    /// Solver options, eg `--options presolve=off,threads=2`
+    #[clap(long, parse(try_from_str = parse_key_values))]
+    options: Option<BTreeMap<String, String>>,
 }
 
 #[derive(Args, Default, Debug, Clone, Serialize, Deserialize)]
@@ -69,6 +76,8 @@ impl Default for Params {
             frob: true,
             baz: false,
             cat: Penum::Bar,
+            seeds: Vec::new(),
+            options: None,
         }
     }
 }
@@ -114,6 +123,10 @@ impl Experiment for MyExperiment {
         &self.outputs
     }
 
+    fn profile(&self) -> Profile {
+        self.profile
+    }
+
     fn new(
         profile: Profile,
         config: Self::Config,
@@ -0,0 +1,268 @@
+//! Hashing which is consistent across runs and platforms, for building IDs without serialising to JSON.
+//!
+//! Unlike [`std::hash::Hash`], whose output may depend on endianness and pointer width, [`ConsistentHash`]
+//! always feeds the hasher the same bytes for the same value.  It is also implemented for `f32` and `f64`.
+use std::collections::{BTreeMap, BTreeSet};
+use std::hash::{Hash, Hasher};
+
+/// SipHash keys used by [`consistent_hash`].
+const KEY0: u64 = 0x6c61_6272_6174_0001;
+const KEY1: u64 = 0x6c61_6272_6174_0002;
+
+/// A value which can be hashed consistently across runs and platforms.  Implement it for structs with
+/// [`impl_consistent_hash!`](crate::impl_consistent_hash).
+pub trait ConsistentHash {
+    fn consistent_hash<H: Hasher>(&self, state: &mut H);
+}
+
+/// Compute the consistent 64-bit SipHash of a value.
+pub fn consistent_hash<T: ConsistentHash + ?Sized>(val: &T) -> u64 {
+    #[allow(deprecated)]
+    let mut hasher = std::hash::SipHasher::new_with_keys(KEY0, KEY1);
+    val.consistent_hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Build an ID string from [`consistent_hash`].  This is much cheaper than [`id_from_serialised`](crate::id_from_serialised)
+/// for large values, since nothing is serialised.  SipHash is not a cryptographic hash and the ID has only 64 bits,
+/// which is plenty to tell experiments apart but not to resist deliberate collisions.
+pub fn id_from_consistent_hash<T: ConsistentHash + ?Sized>(val: &T) -> String {
+    base_62::encode(&consistent_hash(val).to_le_bytes())
+}
+
+/// Build an ID string from a type's [`std::hash::Hash`] implementation, using the same SipHash keys as
+/// [`consistent_hash`].  Convenient for parameter types which already derive `Hash`, but `Hash` implementations may
+/// differ between platforms and Rust versions, so prefer [`id_from_consistent_hash`] for IDs which must be stable.
+pub fn id_from_hash<T: Hash + ?Sized>(val: &T) -> String {
+    #[allow(deprecated)]
+    let mut hasher = std::hash::SipHasher::new_with_keys(KEY0, KEY1);
+    val.hash(&mut hasher);
+    base_62::encode(&hasher.finish().to_le_bytes())
+}
+
+/// Implement [`IdStr`](crate::IdStr) for a type using [`id_from_consistent_hash`], recording
+/// [`IdHashAlgo::SipHash`](crate::IdHashAlgo::SipHash) as its [`IdStr::id_hash_algo`](crate::IdStr::id_hash_algo).
+/// An [`IdStr::id_label`](crate::IdStr::id_label) can be given as a function of the value, and is prepended with
+/// [`with_id_label`](crate::with_id_label).
+///
+/// ```
+/// use labrat::{impl_consistent_hash, impl_id_str_consistent_hash};
+///
+/// #[derive(serde::Serialize)]
+/// struct Params {
+///     epsilon: f64,
+/// }
+///
+/// impl_consistent_hash!(Params { epsilon });
+/// impl_id_str_consistent_hash!(Params);
+///
+/// #[derive(serde::Serialize)]
+/// struct Named {
+///     name: String,
+///     epsilon: f64,
+/// }
+///
+/// impl_consistent_hash!(Named { name, epsilon });
+/// impl_id_str_consistent_hash!(Named, label = |p| Some(p.name.clone()));
+/// ```
+#[macro_export]
+macro_rules! impl_id_str_consistent_hash {
+    ($t:ty) => {
+        impl $crate::IdStr for $t {
+            fn id_hash_algo() -> $crate::IdHashAlgo {
+                $crate::IdHashAlgo::SipHash
+            }
+
+            fn id_str(&self) -> String {
+                $crate::with_id_label(self, $crate::hash::id_from_consistent_hash(self))
+            }
+        }
+    };
+    ($t:ty, label = $label:expr) => {
+        impl $crate::IdStr for $t {
+            fn id_label(&self) -> Option<String> {
+                let label: fn(&$t) -> Option<String> = $label;
+                label(self)
+            }
+
+            fn id_hash_algo() -> $crate::IdHashAlgo {
+                $crate::IdHashAlgo::SipHash
+            }
+
+            fn id_str(&self) -> String {
+                $crate::with_id_label(self, $crate::hash::id_from_consistent_hash(self))
+            }
+        }
+    };
+}
+
+/// Implement [`ConsistentHash`] for a struct by hashing the listed fields in order.
+///
+/// ```
+/// use labrat::impl_consistent_hash;
+///
+/// struct Params {
+///     epsilon: f64,
+///     seeds: Vec<u64>,
+/// }
+///
+/// impl_consistent_hash!(Params { epsilon, seeds });
+/// ```
+#[macro_export]
+macro_rules! impl_consistent_hash {
+    ($t:ty { $($field:ident),* $(,)? }) => {
+        impl $crate::hash::ConsistentHash for $t {
+            fn consistent_hash<H: ::std::hash::Hasher>(&self, state: &mut H) {
+                $( $crate::hash::ConsistentHash::consistent_hash(&self.$field, state); )*
+            }
+        }
+    };
+}
+
+macro_rules! impl_le_bytes {
+    ($($t:ty),*) => {
+        $(
+            impl ConsistentHash for $t {
+                fn consistent_hash<H: Hasher>(&self, state: &mut H) {
+                    state.write(&self.to_le_bytes());
+                }
+            }
+        )*
+    };
+}
+
+impl_le_bytes!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
+
+// `-0.0` is hashed as `0.0`, and every NaN as the same canonical NaN, so that values which compare equal (or are
+// both NaN) hash the same.
+impl ConsistentHash for f32 {
+    fn consistent_hash<H: Hasher>(&self, state: &mut H) {
+        let x = if self.is_nan() {
+            f32::NAN
+        } else if *self == 0.0 {
+            0.0
+        } else {
+            *self
+        };
+        state.write(&x.to_le_bytes());
+    }
+}
+
+impl ConsistentHash for f64 {
+    fn consistent_hash<H: Hasher>(&self, state: &mut H) {
+        let x = if self.is_nan() {
+            f64::NAN
+        } else if *self == 0.0 {
+            0.0
+        } else {
+            *self
+        };
+        state.write(&x.to_le_bytes());
+    }
+}
+
+impl ConsistentHash for usize {
+    fn consistent_hash<H: Hasher>(&self, state: &mut H) {
+        (*self as u64).consistent_hash(state)
+    }
+}
+
+impl ConsistentHash for isize {
+    fn consistent_hash<H: Hasher>(&self, state: &mut H) {
+        (*self as i64).consistent_hash(state)
+    }
+}
+
+impl ConsistentHash for bool {
+    fn consistent_hash<H: Hasher>(&self, state: &mut H) {
+        u8::from(*self).consistent_hash(state)
+    }
+}
+
+impl ConsistentHash for char {
+    fn consistent_hash<H: Hasher>(&self, state: &mut H) {
+        u32::from(*self).consistent_hash(state)
+    }
+}
+
+impl ConsistentHash for str {
+    fn consistent_hash<H: Hasher>(&self, state: &mut H) {
+        self.len().consistent_hash(state);
+        state.write(self.as_bytes());
+    }
+}
+
+impl ConsistentHash for String {
+    fn consistent_hash<H: Hasher>(&self, state: &mut H) {
+        self.as_str().consistent_hash(state)
+    }
+}
+
+impl<T: ConsistentHash + ?Sized> ConsistentHash for &T {
+    fn consistent_hash<H: Hasher>(&self, state: &mut H) {
+        (**self).consistent_hash(state)
+    }
+}
+
+impl<T: ConsistentHash + ?Sized> ConsistentHash for Box<T> {
+    fn consistent_hash<H: Hasher>(&self, state: &mut H) {
+        (**self).consistent_hash(state)
+    }
+}
+
+impl<T: ConsistentHash> ConsistentHash for Option<T> {
+    fn consistent_hash<H: Hasher>(&self, state: &mut H) {
+        match self {
+            None => 0u8.consistent_hash(state),
+            Some(val) => {
+                1u8.consistent_hash(state);
+                val.consistent_hash(state);
+            }
+        }
+    }
+}
+
+impl<T: ConsistentHash> ConsistentHash for [T] {
+    fn consistent_hash<H: Hasher>(&self, state: &mut H) {
+        self.len().consistent_hash(state);
+        self.iter().for_each(|x| x.consistent_hash(state));
+    }
+}
+
+impl<T: ConsistentHash> ConsistentHash for Vec<T> {
+    fn consistent_hash<H: Hasher>(&self, state: &mut H) {
+        self.as_slice().consistent_hash(state)
+    }
+}
+
+impl<T: ConsistentHash> ConsistentHash for BTreeSet<T> {
+    fn consistent_hash<H: Hasher>(&self, state: &mut H) {
+        self.len().consistent_hash(state);
+        self.iter().for_each(|x| x.consistent_hash(state));
+    }
+}
+
+impl<K: ConsistentHash, V: ConsistentHash> ConsistentHash for BTreeMap<K, V> {
+    fn consistent_hash<H: Hasher>(&self, state: &mut H) {
+        self.len().consistent_hash(state);
+        for (k, v) in self {
+            k.consistent_hash(state);
+            v.consistent_hash(state);
+        }
+    }
+}
+
+impl<A: ConsistentHash, B: ConsistentHash> ConsistentHash for (A, B) {
+    fn consistent_hash<H: Hasher>(&self, state: &mut H) {
+        self.0.consistent_hash(state);
+        self.1.consistent_hash(state);
+    }
+}
+
+impl<A: ConsistentHash, B: ConsistentHash, C: ConsistentHash> ConsistentHash for (A, B, C) {
+    fn consistent_hash<H: Hasher>(&self, state: &mut H) {
+        self.0.consistent_hash(state);
+        self.1.consistent_hash(state);
+        self.2.consistent_hash(state);
+    }
+}
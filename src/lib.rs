@@ -2,20 +2,31 @@
 //!
 //! It is primarily for personal use.
 use anyhow::{Context, Result};
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches, Parser};
 use serde::de::DeserializeOwned;
 use sha2::Digest;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::ffi::OsString;
 use std::fmt::{Debug, Display};
 use std::fs::File;
-use std::io::{stdout, BufReader};
+use std::io::{stdout, BufReader, Read, Write};
 use std::path::{Path, PathBuf};
 use std::process::exit;
-use std::time::Duration;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+pub mod hash;
+pub mod sweep;
+#[cfg(test)]
+mod tests;
 
 pub use clap::{ArgEnum, Args};
 pub use serde::{Deserialize, Serialize};
 
-fn read_json<T, P>(path: P) -> Result<T>
+/// Read a file, detecting the format from the file extension (see [`SerFormat::from_path`]).
+fn read_file<T, P>(path: P) -> Result<T>
 where
     T: DeserializeOwned,
     P: AsRef<Path> + Debug,
@@ -24,13 +35,272 @@ where
         .map(BufReader::new)
         .with_context(|| format!("unable to read {:?}", &path))?;
 
-    let x: T = serde_json::from_reader(file)?;
+    let x: T = match SerFormat::from_path(&path) {
+        SerFormat::Json => serde_json::from_reader(file)?,
+        #[cfg(feature = "bincode")]
+        SerFormat::Bincode => bincode::deserialize_from(file)?,
+        #[cfg(feature = "serde_yaml")]
+        SerFormat::Yaml => serde_yaml::from_reader(file)?,
+        #[cfg(feature = "toml")]
+        SerFormat::Toml => toml::from_str(&std::io::read_to_string(file)?)?,
+    };
 
     Ok(x)
 }
 
+/// File format for index and parameter files.  The available variants depend on the enabled features.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum SerFormat {
+    /// Pretty-printed JSON, with the `.json` extension.
+    Json,
+    /// [`bincode`](https://docs.rs/bincode), with the `.bin` extension.  Much faster to read and write than JSON
+    /// for large outputs, but files are not human-readable.  Since bincode doesn't record field names, types which
+    /// skip fields when serialising (eg with `#[serde(skip_serializing_if = ...)]`) can't be read back.  Requires the
+    /// `bincode` feature.
+    #[cfg(feature = "bincode")]
+    Bincode,
+    /// YAML, with the `.yaml` extension.  Requires the `serde_yaml` feature.
+    #[cfg(feature = "serde_yaml")]
+    Yaml,
+    /// TOML, with the `.toml` extension, for hand-written parameter files loaded with `--load-params`.  Only
+    /// supported for reading, so it can't be used as [`Experiment::ser_format`].  Requires the `toml` feature.
+    #[cfg(feature = "toml")]
+    Toml,
+}
+
+impl SerFormat {
+    /// File extension, without the leading `.`
+    pub fn extension(&self) -> &'static str {
+        match self {
+            SerFormat::Json => "json",
+            #[cfg(feature = "bincode")]
+            SerFormat::Bincode => "bin",
+            #[cfg(feature = "serde_yaml")]
+            SerFormat::Yaml => "yaml",
+            #[cfg(feature = "toml")]
+            SerFormat::Toml => "toml",
+        }
+    }
+
+    /// Whether the format can be read without knowing the type, which allows index files to be flattened and to
+    /// record optional information such as the Slurm resources.
+    fn is_self_describing(&self) -> bool {
+        match self {
+            SerFormat::Json => true,
+            #[cfg(feature = "bincode")]
+            SerFormat::Bincode => false,
+            #[cfg(feature = "serde_yaml")]
+            SerFormat::Yaml => true,
+            // TOML has no null
+            #[cfg(feature = "toml")]
+            SerFormat::Toml => false,
+        }
+    }
+
+    /// Detect the format from a file extension, falling back to JSON.
+    pub fn from_path(path: impl AsRef<Path>) -> Self {
+        match path.as_ref().extension().and_then(|e| e.to_str()) {
+            #[cfg(feature = "bincode")]
+            Some("bin") => SerFormat::Bincode,
+            #[cfg(feature = "serde_yaml")]
+            Some("yaml" | "yml") => SerFormat::Yaml,
+            #[cfg(feature = "toml")]
+            Some("toml") => SerFormat::Toml,
+            Some(_) | None => SerFormat::Json,
+        }
+    }
+
+    /// Write a file atomically, by serialising to a temporary file in the same directory and renaming it into place.
+    fn write<T: Serialize + ?Sized>(&self, path: impl AsRef<Path>, val: &T) -> Result<()> {
+        let tmp = self.write_temp(path.as_ref(), val)?;
+        std::fs::rename(&tmp, path)?;
+        Ok(())
+    }
+
+    /// Like [`SerFormat::write`], but leaves an existing file untouched, even if another process is writing the same
+    /// path concurrently.  Returns `true` if the file was written.
+    fn write_new<T: Serialize + ?Sized>(&self, path: impl AsRef<Path>, val: &T) -> Result<bool> {
+        let tmp = self.write_temp(path.as_ref(), val)?;
+        // Unlike rename, hard_link fails if the destination exists.
+        let written = match std::fs::hard_link(&tmp, path) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Ok(false),
+            Err(e) => Err(e),
+        };
+        std::fs::remove_file(&tmp)?;
+        Ok(written?)
+    }
+
+    fn write_temp<T: Serialize + ?Sized>(&self, path: &Path, val: &T) -> Result<PathBuf> {
+        let contents = match self {
+            SerFormat::Json => serde_json::to_vec_pretty(val)?,
+            #[cfg(feature = "bincode")]
+            SerFormat::Bincode => bincode::serialize(val)?,
+            #[cfg(feature = "serde_yaml")]
+            SerFormat::Yaml => serde_yaml::to_string(val)?.into_bytes(),
+            #[cfg(feature = "toml")]
+            SerFormat::Toml => anyhow::bail!("unable to write {:?}: TOML is read-only", path),
+        };
+        // The counter keeps names unique between threads of this process, which may share a timestamp.
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.subsec_nanos());
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let tmp = path.with_file_name(format!(
+            ".{}.{}-{}-{}.tmp",
+            name,
+            std::process::id(),
+            nanos,
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::write(&tmp, contents).with_context(|| format!("unable to write {:?}", &tmp))?;
+        Ok(tmp)
+    }
+}
+
+/// The contents of an index file, as written by [`write_index`].  The top-level keys are fixed, regardless of any
+/// `serde` renaming applied to the user's `Input` and `Output` types, which only affects the keys nested inside
+/// `input` and `output`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Index<I, O> {
+    #[serde(rename = "input")]
+    input: I,
+    #[serde(rename = "output")]
+    output: O,
+    #[serde(rename = "profile", default)]
+    profile: Option<Profile>,
+    #[serde(rename = "slurm", default)]
+    slurm: Option<SlurmResources>,
+    #[serde(rename = "argv", default)]
+    argv: Option<Vec<String>>,
+    #[serde(rename = "id_hash", default)]
+    id_hash: Option<IdHashAlgo>,
+    #[serde(rename = "version", default)]
+    version: u32,
+}
+
+/// Information recorded in an index file other than the experiment itself.
+struct IndexMeta {
+    slurm: Option<SlurmResources>,
+    /// The command-line arguments of the process which wrote the index file
+    argv: Option<Vec<String>>,
+}
+
+fn write_index<T: Experiment>(exp: &T, slurm: Option<SlurmResources>) -> Result<()> {
+    let format = T::ser_format();
+    let p = exp.output_dir()?.join(exp.index_file_name());
+    // SlurmResources skips empty fields when serialising, which bincode can't read back.
+    if slurm.is_some() && !format.is_self_describing() {
+        anyhow::bail!(
+            "Slurm resources can't be recorded in {} index files",
+            format.extension().to_uppercase()
+        )
+    }
+    let contents = Index {
+        input: exp.input(),
+        output: exp.output(),
+        profile: Some(exp.profile()),
+        slurm,
+        argv: Some(std::env::args().collect()),
+        id_hash: Some(T::Parameters::id_hash_algo()),
+        version: T::index_version(),
+    };
+    if T::flatten_index() && format.is_self_describing() {
+        format.write(&p, &flatten_json(serde_json::to_value(&contents)?))?;
+    } else {
+        format.write(&p, &contents)?;
+    }
+    if T::link_latest_index() {
+        link_latest_index(&p)?;
+    }
+    Ok(())
+}
+
+/// The parameter file of an index file: alongside it, or one level up with nested input directories.
+fn parameter_file_for_index(index: &Path, format: SerFormat) -> PathBuf {
+    let param_filename = format!("parameters.{}", format.extension());
+    let param_file = index.with_file_name(&param_filename);
+    if !param_file.exists() {
+        if let Some(dir) = index.parent().and_then(Path::parent) {
+            return dir.join(&param_filename);
+        }
+    }
+    param_file
+}
+
+/// Point `latest-index.EXT` in the same directory at `index`.  Does nothing on platforms without symbolic links.
+fn link_latest_index(index: &Path) -> Result<()> {
+    #[cfg(unix)]
+    {
+        let (Some(name), Some(ext)) = (index.file_name(), index.extension()) else {
+            return Ok(());
+        };
+        let link = index.with_file_name(format!("latest-index.{}", ext.to_string_lossy()));
+        if link.symlink_metadata().is_ok() {
+            std::fs::remove_file(&link)?;
+        }
+        std::os::unix::fs::symlink(name, &link)
+            .with_context(|| format!("unable to create symlink {:?}", &link))?;
+    }
+    #[cfg(not(unix))]
+    let _ = index;
+    Ok(())
+}
+
+fn read_index<T: Experiment>(path: impl AsRef<Path> + Debug) -> Result<(T, IndexMeta)> {
+    let format = SerFormat::from_path(&path);
+    let index: Index<T::Input, T::Output> = if format.is_self_describing() {
+        let mut index: serde_json::Value = read_file(&path)?;
+        if index.get("input").is_none() {
+            index =
+                unflatten_json(index).with_context(|| format!("malformed index {:?}", &path))?;
+        }
+        let version = index.get("version").and_then(|v| v.as_u64()).unwrap_or(0) as u32;
+        if version < T::index_version() {
+            T::migrate_index(&mut index, version);
+        }
+        serde_json::from_value(index)?
+    } else {
+        read_file(&path)?
+    };
+    let Index {
+        input,
+        output,
+        profile,
+        slurm,
+        argv,
+        id_hash,
+        version: _,
+    } = index;
+    // The parameter ID (and hence output paths) can only be reproduced with the algorithm used to write the index.
+    if let Some(id_hash) = id_hash {
+        let current = T::Parameters::id_hash_algo();
+        if id_hash != current {
+            anyhow::bail!(
+                "{:?} was written with parameter IDs hashed by {:?}, but {:?} is in use",
+                path,
+                id_hash,
+                current
+            )
+        }
+    }
+
+    let param_file = parameter_file_for_index(path.as_ref(), format);
+    let params: T::Parameters = read_file(param_file)?;
+    let exp = T::new(
+        profile.unwrap_or_default(),
+        Default::default(),
+        input,
+        params,
+        output,
+    );
+    Ok((exp, IndexMeta { slurm, argv }))
+}
+
 /// A marker type used when there is no Config.
-#[derive(Debug, Copy, Clone, clap::Args, Default)]
+#[derive(Debug, Copy, Clone, clap::Args, Default, Serialize)]
 pub struct NoConfig;
 
 /// The main trait.  A type which implements experiment describes 4 classes of values:
@@ -47,7 +317,7 @@ pub struct NoConfig;
 pub trait Experiment: Sized {
     type Input: Args + Serialize + DeserializeOwned + IdStr;
     type Parameters: Args + Serialize + DeserializeOwned + IdStr;
-    type Config: Args + Default;
+    type Config: Args + Default + Serialize;
     type Output: Serialize + DeserializeOwned;
 
     /// Experiment inputs
@@ -59,6 +329,9 @@ pub trait Experiment: Sized {
     /// Experiment parameters
     fn parameter(&self) -> &Self::Parameters;
 
+    /// The profile the experiment was constructed with, ie the one passed to [`Experiment::new`].
+    fn profile(&self) -> Profile;
+
     /// Construct a new experiment from its parts
     fn new(
         prof: Profile,
@@ -76,9 +349,32 @@ pub trait Experiment: Sized {
         config: &Self::Config,
     ) -> Self::Output;
 
-    /// The root directory for outputs
+    /// A fallible version of [`Experiment::new_output`], for when deriving the output depends on fallible logic such
+    /// as reading an input file.  The default calls [`Experiment::new_output`].
+    fn try_new_output(
+        inputs: &Self::Input,
+        params: &Self::Parameters,
+        config: &Self::Config,
+    ) -> Result<Self::Output> {
+        Ok(Self::new_output(inputs, params, config))
+    }
+
+    /// The root directory for outputs.  May be overridden at runtime with the `LABRAT_ROOT` environment variable, or
+    /// with [`with_root`], which takes precedence over both.
     fn root_dir() -> PathBuf;
 
+    /// The root directory for input data files, if any.  May be overridden with the `LABRAT_INPUT_ROOT` environment
+    /// variable.  Default is none.
+    fn input_root() -> Option<PathBuf> {
+        None
+    }
+
+    /// Resolve the path of an input data file against [`Experiment::input_root`].  If there is no input root, `name`
+    /// is returned unchanged.
+    fn input_path(name: impl AsRef<Path>) -> PathBuf {
+        resolve_input_path::<Self>(std::env::var_os("LABRAT_INPUT_ROOT"), name.as_ref())
+    }
+
     /// A hook for modifying parameters and config after parsing from command-line arguments.
     fn post_parse(
         _prof: Profile,
@@ -88,75 +384,443 @@ pub trait Experiment: Sized {
     ) {
     }
 
+    /// A fallible version of [`Experiment::post_parse`], which can be used to reject invalid combinations of
+    /// inputs, parameters and config.  The default calls [`Experiment::post_parse`].
+    fn try_post_parse(
+        prof: Profile,
+        inputs: &Self::Input,
+        parameters: &mut Self::Parameters,
+        config: &mut Self::Config,
+    ) -> Result<()> {
+        Self::post_parse(prof, inputs, parameters, config);
+        Ok(())
+    }
+
+    /// Return a copy of this experiment with different parameters.  The outputs are re-derived from `config` with
+    /// [`Experiment::try_new_output`], since the config is not retained by the experiment.
+    fn with_parameter(self, parameters: Self::Parameters, config: Self::Config) -> Result<Self>
+    where
+        Self::Input: Clone,
+    {
+        let inputs = self.input().clone();
+        let outputs = Self::try_new_output(&inputs, &parameters, &config)?;
+        Ok(Self::new(
+            self.profile(),
+            config,
+            inputs,
+            parameters,
+            outputs,
+        ))
+    }
+
+    /// Return a copy of this experiment with different inputs.  The outputs are re-derived as in
+    /// [`Experiment::with_parameter`].
+    fn with_input(self, inputs: Self::Input, config: Self::Config) -> Result<Self>
+    where
+        Self::Parameters: Clone,
+    {
+        let parameters = self.parameter().clone();
+        let outputs = Self::try_new_output(&inputs, &parameters, &config)?;
+        Ok(Self::new(
+            self.profile(),
+            config,
+            inputs,
+            parameters,
+            outputs,
+        ))
+    }
+
     /// Given a base filename, return the full path to where the file should be placed.  
     ///
-    /// Eg, for `filename`, returns `ROOT/PARAM_ID/filename`
-    fn get_output_path(&self, filename: &str) -> PathBuf {
-        let mut log_dir = Self::root_dir();
-        log_dir.push(self.parameter().id_str());
-        let mut log_dir = ensure_directory_exists(log_dir).unwrap();
-        log_dir.push(filename);
-        log_dir
+    /// Eg, for `filename`, returns `ROOT/PARAM_ID/filename`.  The parameter directory is created if it doesn't exist.
+    fn get_output_path(&self, filename: &str) -> Result<PathBuf> {
+        let mut path = ensure_directory_exists(param_dir_path(self))?;
+        path.push(filename);
+        Ok(path)
+    }
+
+    /// Check that the root directory exists (creating it if necessary) and is writable, by creating and removing a
+    /// temporary file.  Call this at startup to fail early rather than after the experiment has run.
+    fn check_writable() -> Result<()> {
+        let root = resolve_root_dir::<Self>();
+        let root = ensure_directory_exists(&root)
+            .with_context(|| format!("unable to create root directory {:?}", &root))?;
+        let probe = root.join(format!(".labrat-write-check-{}", std::process::id()));
+        File::create(&probe)
+            .with_context(|| format!("root directory {:?} is not writable", &root))?;
+        std::fs::remove_file(&probe)?;
+        Ok(())
+    }
+
+    /// A directory for checkpoints, `ROOT/PARAM_ID/checkpoints/`, created if it doesn't exist.
+    fn checkpoint_dir(&self) -> Result<PathBuf> {
+        ensure_directory_exists(self.get_output_path("checkpoints")?)
+    }
+
+    /// Given a base filename, return the path where the file should be placed in [`Experiment::output_dir`],
+    /// relative to the root directory.
+    ///
+    /// Eg, for `filename`, returns `PARAM_ID/filename`, or `PARAM_ID/INPUT_ID/filename` if
+    /// [`Experiment::nested_input_dirs`] is `true`.
+    fn relative_output_path(&self, filename: &str) -> PathBuf {
+        let dir = output_dir_path(self);
+        let mut path = dir
+            .strip_prefix(resolve_root_dir::<Self>())
+            .expect("output directory is under the root directory")
+            .to_path_buf();
+        path.push(filename);
+        path
     }
 
     /// Given a base filename, return the full path to where the file should be placed.  The filename
     /// is first prefixed with `self.input().id_str()`.
     ///
     /// Eg, if `filename` is `-hello.txt`, returns `ROOT/PARAM_ID/INPUT_ID-hello.txt`
-    fn get_output_path_prefixed(&self, filename: &str) -> PathBuf {
-        let mut log_dir = Self::root_dir();
-        log_dir.push(self.parameter().id_str());
-        let mut log_dir = ensure_directory_exists(log_dir).unwrap();
-        log_dir.push(format!("{}{}", self.input().id_str(), filename));
-        log_dir
+    ///
+    /// If [`Experiment::nested_input_dirs`] is `true`, the file is placed in `ROOT/PARAM_ID/INPUT_ID/` instead.
+    fn get_output_path_prefixed(&self, filename: &str) -> Result<PathBuf> {
+        let mut path = self.output_dir()?;
+        path.push(format!("{}{}", self.input().id_str(), filename));
+        Ok(path)
     }
 
-    /// Write the index file to the output directory.
-    fn write_index_file(&self) -> Result<()> {
-        let p = self.get_output_path_prefixed("-index.json");
-        let contents = serde_json::json!({
-            "input": self.input(),
-            "output" : self.output(),
-        });
-        let contents = serde_json::to_string_pretty(&contents)?;
-        std::fs::write(p, contents)?;
+    /// Whether each input gets its own subdirectory of the parameter directory, `ROOT/PARAM_ID/INPUT_ID/`, for
+    /// outputs and the index file.  The parameter file stays in `ROOT/PARAM_ID/`.  Useful when there are many inputs
+    /// per parameter set.  Default is `false`.
+    fn nested_input_dirs() -> bool {
+        false
+    }
+
+    /// The directory outputs and the index file are placed in, created if it doesn't exist: `ROOT/PARAM_ID/`, or
+    /// `ROOT/PARAM_ID/INPUT_ID/` if [`Experiment::nested_input_dirs`] is `true`.
+    fn output_dir(&self) -> Result<PathBuf> {
+        ensure_directory_exists(output_dir_path(self))
+    }
+
+    /// Full paths of all output files declared in [`Experiment::output()`].  Every string found in the serialised
+    /// output (including inside nested structs, sequences and present `Option`s) is treated as a filename in
+    /// [`Experiment::output_dir`].
+    fn output_file_paths(&self) -> Vec<PathBuf> {
+        let output = serde_json::to_value(self.output()).unwrap();
+        let mut filenames = Vec::new();
+        collect_strings(&output, &mut filenames);
+        let dir = output_dir_path(self);
+        filenames.into_iter().map(|f| dir.join(f)).collect()
+    }
+
+    /// Check whether all declared output files exist.
+    fn status(&self) -> Status {
+        let missing: Vec<_> = self
+            .output_file_paths()
+            .into_iter()
+            .filter(|p| !p.exists())
+            .collect();
+        if missing.is_empty() {
+            Status::Complete
+        } else {
+            Status::Incomplete { missing }
+        }
+    }
+
+    /// Whether all declared output files exist.  Partial outputs count as incomplete.  See [`Experiment::status`].
+    fn is_complete(&self) -> bool {
+        self.status() == Status::Complete
+    }
+
+    /// Files the outputs are derived from, used by [`Experiment::is_stale`].  The default is none.
+    fn input_files(&self) -> Vec<PathBuf> {
+        Vec::new()
+    }
+
+    /// Whether the outputs need to be (re)computed: either an output file is missing, or an output is older than one
+    /// of the [`Experiment::input_files`].
+    fn is_stale(&self) -> Result<bool> {
+        let mut oldest_output = None;
+        for path in self.output_file_paths() {
+            if !path.exists() {
+                return Ok(true);
+            }
+            let modified = std::fs::metadata(&path)?.modified()?;
+            oldest_output = Some(oldest_output.map_or(modified, |t: SystemTime| t.min(modified)));
+        }
+        let oldest_output = match oldest_output {
+            Some(t) => t,
+            None => return Ok(false),
+        };
+        for path in self.input_files() {
+            let modified = std::fs::metadata(&path)
+                .and_then(|m| m.modified())
+                .with_context(|| format!("unable to read modification time of {:?}", &path))?;
+            if modified > oldest_output {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Total size in bytes of all files in the parameter directory (`ROOT/PARAM_ID/`), including subdirectories.
+    /// Symbolic links are not followed.
+    fn output_dir_size(&self) -> Result<u64> {
+        dir_size(self.get_output_path("")?)
+    }
+
+    /// Set the modification time of an output file to now, creating it if necessary.  Requires the `testing`
+    /// feature.
+    #[cfg(feature = "testing")]
+    fn touch_output(&self, filename: &str) -> Result<()> {
+        touch(self.get_output_path(filename)?)
+    }
+
+    /// Set the modification time of all declared output files to now, creating them if necessary.  Requires the
+    /// `testing` feature.
+    #[cfg(feature = "testing")]
+    fn touch_all_outputs(&self) -> Result<()> {
+        self.output_dir()?;
+        self.output_file_paths().into_iter().try_for_each(touch)
+    }
+
+    /// Whether existing output files should be protected from being overwritten.  If `true`, constructing the experiment
+    /// to run from command-line arguments fails when any of [`Experiment::output_file_paths`] already exists, unless
+    /// `--overwrite-outputs` is passed.  The pipe server, which only computes resources, doesn't check.  Default is
+    /// `false`.
+    fn protect_outputs() -> bool {
+        false
+    }
+
+    /// Return an error if any of the declared output files already exist.
+    fn check_outputs_absent(&self) -> Result<()> {
+        let existing: Vec<_> = self
+            .output_file_paths()
+            .into_iter()
+            .filter(|p| p.exists())
+            .collect();
+        if !existing.is_empty() {
+            anyhow::bail!(
+                "output files already exist (use --overwrite-outputs to overwrite): {:?}",
+                existing
+            )
+        }
+        Ok(())
+    }
+
+    /// Whether the index file should use a flat key space, eg `"input.index"` and `"output.log"`, rather than nested
+    /// `"input"` and `"output"` objects.  Default is `false`.  [`Experiment::from_index_file`] reads either layout.
+    fn flatten_index() -> bool {
+        false
+    }
+
+    /// The version of the index file layout, recorded in index files.  Increase it when `Input` or `Output` change
+    /// incompatibly, and upgrade old index files in [`Experiment::migrate_index`].  Default is 0.
+    fn index_version() -> u32 {
+        0
+    }
+
+    /// Upgrade an index file written with an older [`Experiment::index_version`] (0 if none was recorded), before
+    /// it is deserialised.  Only called for self-describing formats such as JSON.  Default does nothing.
+    fn migrate_index(_value: &mut serde_json::Value, _from_version: u32) {}
+
+    /// Whether writing an index file also points a `latest-index` symbolic link in the parameter directory at it.
+    /// Default is `false`.
+    fn link_latest_index() -> bool {
+        false
+    }
+
+    /// The format used when writing index and parameter files.  Default is [`SerFormat::Json`].  When reading,
+    /// the format is detected from the file extension.
+    fn ser_format() -> SerFormat {
+        SerFormat::Json
+    }
+
+    /// A hook called by [`Experiment::run_experiment`] after the experiment body succeeds, eg to write a summary or a
+    /// marker file.  Default does nothing.
+    fn post_run(&self) -> Result<()> {
         Ok(())
     }
 
-    /// Write the parameter file to the output directory.
+    /// Run the experiment body `f`, then [`Experiment::post_run`], then write the parameter file and finally the
+    /// index file.  If `f` or `post_run` fails, the error is returned and nothing further is run or written, so an
+    /// index file is only present for experiments which finished.
+    fn run_experiment(&self, f: impl FnOnce(&Self) -> Result<()>) -> Result<()> {
+        f(self)?;
+        self.post_run()?;
+        self.write_parameter_file()?;
+        self.write_index_file()
+    }
+
+    /// The filename of the index file in [`Experiment::output_dir`].  Default is `INPUT_ID-index.EXT`, or `index.EXT`
+    /// with [`Experiment::nested_input_dirs`], where `EXT` is the extension of [`Experiment::ser_format`].
+    /// [`Experiment::from_index_file`] detects the format from the extension, so overrides should keep it, and the
+    /// tools in [`sweep`] only find index files named `*-index.EXT` (or `INPUT_ID/index.EXT`).
+    fn index_file_name(&self) -> String {
+        if Self::nested_input_dirs() {
+            return format!("index.{}", Self::ser_format().extension());
+        }
+        format!(
+            "{}-index.{}",
+            self.input().id_str(),
+            Self::ser_format().extension()
+        )
+    }
+
+    /// Write the index file to the output directory, named by [`Experiment::index_file_name`].
+    fn write_index_file(&self) -> Result<()> {
+        write_index(self, None)
+    }
+
+    /// Record the named environment variables in `env.json` in the parameter directory.  Variables which are not set
+    /// are recorded as `null`.
+    fn write_env_file(&self, vars: &[&str]) -> Result<()> {
+        let env: serde_json::Map<_, _> = vars
+            .iter()
+            .map(|&v| {
+                let val = std::env::var(v)
+                    .ok()
+                    .map_or(serde_json::Value::Null, Into::into);
+                (v.to_string(), val)
+            })
+            .collect();
+        SerFormat::Json.write(self.get_output_path("env.json")?, &env)
+    }
+
+    /// Whether [`Experiment::write_parameter_file`] checks an existing parameter file against the parameters.  This
+    /// reads the file back each time, so it is opt-in.  Default is `false`.
+    fn verify_parameter_file() -> bool {
+        false
+    }
+
+    /// Write the parameter file to the output directory.  If the file already exists it is left untouched, and if
+    /// [`Experiment::verify_parameter_file`] is `true`, an error is returned if it contains different parameters,
+    /// since this means two parameter sets share an ID (eg via a user-chosen name) and would clobber each other's
+    /// outputs.
     fn write_parameter_file(&self) -> Result<()> {
-        let p = self.get_output_path("parameters.json");
-        if !p.exists() {
-            std::fs::write(p, serde_json::to_string_pretty(self.parameter())?)?;
+        let format = Self::ser_format();
+        let p = self.get_output_path(&format!("parameters.{}", format.extension()))?;
+        if !p.exists() && format.write_new(&p, self.parameter())? {
+            return Ok(());
+        }
+        if Self::verify_parameter_file() {
+            let existing: Self::Parameters = read_file(&p)?;
+            if serde_json::to_value(&existing)? != serde_json::to_value(self.parameter())? {
+                anyhow::bail!(
+                    "parameter ID collision: {:?} contains different parameters with the same ID `{}`",
+                    p,
+                    self.parameter().id_str()
+                )
+            }
         }
         Ok(())
     }
 
-    /// Instantiate an experiment from disk
+    /// Instantiate an experiment from disk.  The parameter file is expected to be alongside the index file
+    /// and use the same format.  The experiment is constructed with the profile recorded in the index file
+    /// (or [`Profile::Default`] if there is none) and the default config.
     fn from_index_file(path: impl AsRef<Path> + Debug) -> Result<Self> {
-        #[derive(Debug, Clone, Deserialize)]
-        struct Index<I, O> {
-            input: I,
-            output: O,
+        read_index(path).map(|(exp, _)| exp)
+    }
+
+    /// Construct a new experiment from command-line arguments.
+    /// May exit the program.
+    fn from_cl_args() -> Result<Self> {
+        exit_on_cl_outcome(Self::try_from_args(std::env::args_os()))
+    }
+
+    /// Construct a new experiment from the given command-line arguments (including `argv[0]`).  Unlike
+    /// [`Experiment::from_cl_args`], this never exits the program.
+    fn try_from_args<I, A>(args: I) -> Result<ClOutcome<Self>>
+    where
+        I: IntoIterator<Item = A>,
+        A: Into<OsString> + Clone,
+    {
+        let args: Vec<OsString> = args.into_iter().map(Into::into).collect();
+        if ClArgs::<NoSlurmArgs, Self>::run_pre_parse_tools(&args)? {
+            return Ok(ClOutcome::Exit);
         }
 
-        let index: Index<Self::Input, Self::Output> = read_json(&path)?;
-        let Index { input, output } = index;
+        let args = ClArgs::<NoSlurmArgs, Self>::try_parse_checked(args)?;
+        let tools = args.tools.clone();
+        let overwrite_outputs = args.overwrite_outputs;
+        let exp = args.into_experiment()?;
+        if tools.run(&exp)? {
+            return Ok(ClOutcome::Exit);
+        }
+        if Self::protect_outputs() && !overwrite_outputs {
+            exp.check_outputs_absent()?;
+        }
+        Ok(ClOutcome::Experiment(exp))
+    }
+}
 
-        let param_file = path.as_ref().with_file_name("parameters.json");
-        let params: Self::Parameters = read_json(param_file)?;
-        Ok(Self::new(
-            Profile::Default,
-            Default::default(),
-            input,
-            params,
-            output,
-        ))
+/// The result of parsing command-line arguments without exiting the program, see [`Experiment::try_from_args`]
+/// and [`ResourcePolicy::try_from_args_with_slurm`].
+#[derive(Debug)]
+pub enum ClOutcome<T> {
+    /// An experiment was constructed and should be run.
+    Experiment(T),
+    /// Information was printed (eg `--slurminfo`) or the pipe server was run; the program should exit.
+    Exit,
+}
+
+/// Unwrap a [`ClOutcome`], exiting the program with status 0 on [`ClOutcome::Exit`].  Command-line parsing errors
+/// (including `--help`) are printed by clap before exiting.
+fn exit_on_cl_outcome<T>(outcome: Result<ClOutcome<T>>) -> Result<T> {
+    match outcome {
+        Ok(ClOutcome::Experiment(exp)) => Ok(exp),
+        Ok(ClOutcome::Exit) => exit(0),
+        Err(e) => match e.downcast::<clap::Error>() {
+            Ok(e) => e.exit(),
+            Err(e) => Err(e),
+        },
     }
+}
 
-    /// Construct a new experiment from command-line arguments.
-    fn from_cl_args() -> Result<Self> {
-        ClArgs::<NoSlurmArgs, Self>::parse().into_experiment()
+/// Completion status of an experiment, see [`Experiment::status`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Status {
+    /// All declared output files exist.
+    Complete,
+    /// Some declared output files are missing.
+    Incomplete { missing: Vec<PathBuf> },
+}
+
+thread_local! {
+    static ROOT_DIR_OVERRIDE: RefCell<Option<PathBuf>> = const { RefCell::new(None) };
+}
+
+/// Run `f` with the output root directory of all experiments set to `root` instead of [`Experiment::root_dir`].
+/// The override only applies to the current thread and is removed when `f` returns, which makes it suitable
+/// for pointing tests at a temporary directory.
+pub fn with_root<R>(root: impl Into<PathBuf>, f: impl FnOnce() -> R) -> R {
+    struct Restore(Option<PathBuf>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            ROOT_DIR_OVERRIDE.with(|r| *r.borrow_mut() = self.0.take());
+        }
+    }
+
+    let _restore = Restore(ROOT_DIR_OVERRIDE.with(|r| r.replace(Some(root.into()))));
+    f()
+}
+
+/// The root directory in effect.  In order of precedence: the [`with_root`] override, the `LABRAT_ROOT` environment
+/// variable, then [`Experiment::root_dir`].
+fn resolve_root_dir<T: Experiment>() -> PathBuf {
+    resolve_root_dir_from::<T>(std::env::var_os("LABRAT_ROOT"))
+}
+
+/// [`resolve_root_dir`] with `env_root` in place of the `LABRAT_ROOT` environment variable.
+fn resolve_root_dir_from<T: Experiment>(env_root: Option<OsString>) -> PathBuf {
+    ROOT_DIR_OVERRIDE
+        .with(|r| r.borrow().clone())
+        .or_else(|| env_root.map(PathBuf::from))
+        .unwrap_or_else(T::root_dir)
+}
+
+/// [`Experiment::input_path`] with `env_root` in place of the `LABRAT_INPUT_ROOT` environment variable.
+fn resolve_input_path<T: Experiment>(env_root: Option<OsString>, name: &Path) -> PathBuf {
+    match env_root.map(PathBuf::from).or_else(T::input_root) {
+        Some(root) => root.join(name),
+        None => name.to_path_buf(),
     }
 }
 
@@ -164,9 +828,392 @@ pub trait Experiment: Sized {
 /// which are [`Serialize`].  Note this may produce collisions, but it is
 /// extremely unlikely.
 pub fn id_from_serialised<T: Serialize + ?Sized>(val: &T) -> String {
+    id_from_serialised_with(val, IdHashAlgo::Sha224)
+}
+
+/// Like [`id_from_serialised`], but with the given hash algorithm.
+pub fn id_from_serialised_with<T: Serialize + ?Sized>(val: &T, algo: IdHashAlgo) -> String {
+    let json = serde_json::to_string(val).unwrap();
+    match algo {
+        IdHashAlgo::Sha224 => {
+            let mut hasher = sha2::Sha224::new();
+            hasher.update(&json);
+            base_62::encode(hasher.finalize().as_slice())
+        }
+        IdHashAlgo::Sha256Trunc => {
+            let mut hasher = sha2::Sha256::new();
+            hasher.update(&json);
+            base_62::encode(&hasher.finalize()[..16])
+        }
+        IdHashAlgo::SipHash => hash::id_from_consistent_hash(json.as_str()),
+    }
+}
+
+/// Like [`id_from_serialised`], but object keys are sorted first, so the ID doesn't depend on the order fields are
+/// declared in.  Adding or removing a field still changes the ID.
+pub fn id_from_serialised_canonical<T: Serialize + ?Sized>(val: &T) -> String {
+    fn canonical(value: serde_json::Value) -> serde_json::Value {
+        match value {
+            serde_json::Value::Object(map) => {
+                let mut entries: Vec<_> = map.into_iter().collect();
+                entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+                entries
+                    .into_iter()
+                    .map(|(k, v)| (k, canonical(v)))
+                    .collect::<serde_json::Map<_, _>>()
+                    .into()
+            }
+            serde_json::Value::Array(vals) => vals.into_iter().map(canonical).collect(),
+            v => v,
+        }
+    }
+    id_from_serialised(&canonical(serde_json::to_value(val).unwrap()))
+}
+
+/// Like [`id_from_serialised`], but only the first `bytes` bytes of the SHA-224 digest (at most 28) are encoded,
+/// for shorter paths.  Implement [`IdStr::id_str`] with this to opt in.  The ID carries `8 * bytes` bits, so among
+/// `n` IDs the chance of any collision is roughly `n^2 / 2^(8 * bytes + 1)`.  For a million IDs:
+///
+/// | `bytes` | bits | ID length (about) | collision probability |
+/// |---------|------|-------------------|-----------------------|
+/// | 6       | 48   | 9                 | 2e-3                  |
+/// | 8       | 64   | 11                | 3e-8                  |
+/// | 10      | 80   | 14                | 4e-13                 |
+/// | 12      | 96   | 17                | 6e-18                 |
+///
+/// Panics if `bytes` is 0, which would give every value the same empty ID.
+pub fn id_from_serialised_truncated<T: Serialize + ?Sized>(val: &T, bytes: usize) -> String {
+    assert!(
+        bytes > 0,
+        "truncated IDs need at least one byte of the digest"
+    );
+    let json = serde_json::to_string(val).unwrap();
     let mut hasher = sha2::Sha224::new();
-    hasher.update(&serde_json::to_string(val).unwrap());
-    base_62::encode(hasher.finalize().as_slice())
+    hasher.update(&json);
+    let digest = hasher.finalize();
+    base_62::encode(&digest[..bytes.min(digest.len())])
+}
+
+/// A hash algorithm for building IDs, see [`id_from_serialised_with`] and [`IdStr::id_hash_algo`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IdHashAlgo {
+    /// SHA-224 (the default)
+    Sha224,
+    /// SHA-256, truncated to 128 bits
+    Sha256Trunc,
+    /// 64-bit SipHash, see [`hash::id_from_consistent_hash`]
+    SipHash,
+}
+
+/// Like [`id_from_serialised`], but only fields which differ from `T::default()` contribute to the ID.  Adding a
+/// new field with a default value therefore leaves existing IDs unchanged.
+pub fn id_from_non_default<T: Serialize + Default>(val: &T) -> String {
+    let value = flatten_json(serde_json::to_value(val).unwrap());
+    let default = flatten_json(serde_json::to_value(T::default()).unwrap());
+    match (value, &default) {
+        (serde_json::Value::Object(map), serde_json::Value::Object(default)) => {
+            let changed: serde_json::Map<_, _> = map
+                .into_iter()
+                .filter(|(k, v)| default.get(k) != Some(v))
+                .collect();
+            id_from_serialised(&changed)
+        }
+        (value, _) => id_from_serialised(&value),
+    }
+}
+
+/// A field of [`Experiment::Output`] which differs between two experiments.  The key is a dotted path as produced
+/// by flattening nested objects, and a value is `None` if the field is absent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OutputDiff {
+    pub key: String,
+    pub a: Option<serde_json::Value>,
+    pub b: Option<serde_json::Value>,
+}
+
+impl Display for OutputDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let show = |v: &Option<serde_json::Value>| match v {
+            Some(v) => v.to_string(),
+            None => "<missing>".to_string(),
+        };
+        write!(f, "{}: {} != {}", self.key, show(&self.a), show(&self.b))
+    }
+}
+
+/// Compare the serialised outputs of two experiments field-by-field.
+pub fn diff_outputs<T: Experiment>(a: &T, b: &T) -> Result<Vec<OutputDiff>> {
+    let as_map = |exp: &T| -> Result<serde_json::Map<String, serde_json::Value>> {
+        Ok(match flatten_json(serde_json::to_value(exp.output())?) {
+            serde_json::Value::Object(map) => map,
+            v => std::iter::once((String::new(), v)).collect(),
+        })
+    };
+    let a = as_map(a)?;
+    let mut b = as_map(b)?;
+    let mut diffs = Vec::new();
+    for (key, a_val) in a {
+        let b_val = b.remove(&key);
+        if b_val.as_ref() != Some(&a_val) {
+            diffs.push(OutputDiff {
+                key,
+                a: Some(a_val),
+                b: b_val,
+            });
+        }
+    }
+    for (key, b_val) in b {
+        diffs.push(OutputDiff {
+            key,
+            a: None,
+            b: Some(b_val),
+        });
+    }
+    Ok(diffs)
+}
+
+/// Check a hand-written JSON parameter file against `P`, returning a description of each problem found: fields
+/// which are missing or have the wrong type, and fields which `P` does not have.
+pub fn validate_params_file<P>(path: impl AsRef<Path> + Debug) -> Result<Vec<String>>
+where
+    P: Serialize + DeserializeOwned,
+{
+    let contents =
+        std::fs::read_to_string(&path).with_context(|| format!("unable to read {:?}", &path))?;
+    let given: serde_json::Value = match serde_json::from_str(&contents) {
+        Ok(v) => v,
+        Err(e) => return Ok(vec![format!("invalid JSON: {}", e)]),
+    };
+    let params: P = match serde_json::from_str(&contents) {
+        Ok(p) => p,
+        Err(e) => return Ok(vec![e.to_string()]),
+    };
+
+    let mut problems = Vec::new();
+    let known = flatten_json(serde_json::to_value(&params)?);
+    if let (serde_json::Value::Object(given), serde_json::Value::Object(known)) =
+        (flatten_json(given), &known)
+    {
+        for key in given.keys().filter(|k| !known.contains_key(*k)) {
+            let field = key.rsplit('.').next().unwrap_or(key);
+            match contents.find(&format!("\"{}\"", field)) {
+                Some(i) => problems.push(format!(
+                    "unknown field `{}` at line {}",
+                    key,
+                    contents[..i].matches('\n').count() + 1
+                )),
+                None => problems.push(format!("unknown field `{}`", key)),
+            }
+        }
+    }
+    Ok(problems)
+}
+
+/// A command with only the arguments declared by `P`, without the `--help` and `--version` flags clap adds itself.
+fn declared_args_command<P: Args>() -> clap::Command<'static> {
+    let mut cmd = P::augment_args(
+        clap::Command::new("parameters")
+            .disable_help_flag(true)
+            .disable_version_flag(true),
+    );
+    cmd.build();
+    cmd
+}
+
+/// Metadata for a command-line argument, see [`param_metadata`].
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct ArgMeta {
+    /// The field name, ie the argument ID with the `-`s clap's derive inserts replaced by `_`
+    pub name: String,
+    /// The long flag, without the leading `--`
+    pub long: Option<String>,
+    pub help: Option<String>,
+    pub default: Vec<String>,
+    pub value_names: Vec<String>,
+    /// Whether the argument is a flag which takes no value
+    pub switch: bool,
+}
+
+/// Metadata for each argument of `P` (typically [`Experiment::Parameters`]), as declared with `#[clap(...)]`.  Useful
+/// for generating forms or documentation without parsing the help text.
+pub fn param_metadata<P: Args>() -> Vec<ArgMeta> {
+    declared_args_command::<P>()
+        .get_arguments()
+        .map(|a| ArgMeta {
+            name: a.get_id().replace('-', "_"),
+            long: a.get_long().map(String::from),
+            help: a.get_help().map(String::from),
+            default: a
+                .get_default_values()
+                .iter()
+                .map(|v| v.to_string_lossy().into_owned())
+                .collect(),
+            value_names: a
+                .get_value_names()
+                .unwrap_or_default()
+                .iter()
+                .map(|v| v.to_string())
+                .collect(),
+            switch: !a.is_takes_value_set(),
+        })
+        .collect()
+}
+
+/// Check that the command-line defaults of `P` agree with `P::default()`, returning an error describing the
+/// fields which differ.  Fails if `P` has required arguments.
+pub fn check_defaults_match<P>() -> Result<()>
+where
+    P: Args + Default + Serialize,
+{
+    #[derive(clap::Parser)]
+    struct DefaultArgs<P: Args> {
+        #[clap(flatten)]
+        inner: P,
+    }
+
+    let cl_default = DefaultArgs::<P>::try_parse_from(["defaults"])?.inner;
+    let cl_default = serde_json::to_value(&cl_default)?;
+    let default = serde_json::to_value(P::default())?;
+
+    let mut drift = Vec::new();
+    match (&cl_default, &default) {
+        (serde_json::Value::Object(a), serde_json::Value::Object(b)) => {
+            for (key, a_val) in a {
+                let b_val = b.get(key).unwrap_or(&serde_json::Value::Null);
+                if a_val != b_val {
+                    drift.push(format!(
+                        "{}: {} (command-line) != {} (Default)",
+                        key, a_val, b_val
+                    ));
+                }
+            }
+        }
+        (a, b) if a != b => drift.push(format!("{} (command-line) != {} (Default)", a, b)),
+        _ => {}
+    }
+
+    if !drift.is_empty() {
+        anyhow::bail!("default values differ:\n{}", drift.join("\n"))
+    }
+    Ok(())
+}
+
+/// Panicking version of [`check_defaults_match`], for use in tests.
+pub fn assert_defaults_match<P>()
+where
+    P: Args + Default + Serialize,
+{
+    if let Err(e) = check_defaults_match::<P>() {
+        panic!("{}", e)
+    }
+}
+
+/// Parse a comma-separated list of `KEY=VALUE` pairs, eg `a=1,b=2`.  Intended as a custom clap parser for
+/// parameters, eg `#[clap(long, parse(try_from_str = labrat::parse_key_values))]`.
+pub fn parse_key_values(s: &str) -> Result<BTreeMap<String, String>> {
+    s.split(',')
+        .filter(|kv| !kv.is_empty())
+        .map(|kv| {
+            let (k, v) = kv
+                .split_once('=')
+                .ok_or_else(|| anyhow::anyhow!("expected KEY=VALUE, got `{}`", kv))?;
+            Ok((k.trim().to_string(), v.trim().to_string()))
+        })
+        .collect()
+}
+
+fn collect_strings<'a>(value: &'a serde_json::Value, acc: &mut Vec<&'a str>) {
+    use serde_json::Value;
+    match value {
+        Value::String(s) => acc.push(s),
+        Value::Array(vals) => vals.iter().for_each(|v| collect_strings(v, acc)),
+        Value::Object(map) => map.values().for_each(|v| collect_strings(v, acc)),
+        Value::Null | Value::Bool(_) | Value::Number(_) => {}
+    }
+}
+
+/// Flatten nested objects into a single object with `.`-separated keys.  Non-object values and empty objects are
+/// kept as-is, so that [`unflatten_json`] can restore them.
+fn flatten_json(value: serde_json::Value) -> serde_json::Value {
+    fn flatten_into(
+        prefix: String,
+        value: serde_json::Value,
+        acc: &mut serde_json::Map<String, serde_json::Value>,
+    ) {
+        match value {
+            serde_json::Value::Object(map) if !map.is_empty() => {
+                for (k, v) in map {
+                    flatten_into(format!("{}.{}", prefix, k), v, acc);
+                }
+            }
+            v => {
+                acc.insert(prefix, v);
+            }
+        }
+    }
+
+    match value {
+        serde_json::Value::Object(map) => {
+            let mut acc = serde_json::Map::new();
+            for (k, v) in map {
+                flatten_into(k, v, &mut acc);
+            }
+            serde_json::Value::Object(acc)
+        }
+        v => v,
+    }
+}
+
+/// Inverse of [`flatten_json`].
+fn unflatten_json(value: serde_json::Value) -> Result<serde_json::Value> {
+    use serde_json::{Map, Value};
+    let flat = match value {
+        Value::Object(map) => map,
+        v => return Ok(v),
+    };
+    let mut root = Map::new();
+    for (key, val) in flat {
+        let mut parts: Vec<&str> = key.split('.').collect();
+        let last = parts.pop().unwrap();
+        let mut node = &mut root;
+        for part in parts {
+            node = match node
+                .entry(part)
+                .or_insert_with(|| Value::Object(Map::new()))
+            {
+                Value::Object(m) => m,
+                _ => anyhow::bail!("conflicting key `{}`", key),
+            };
+        }
+        node.insert(last.to_string(), val);
+    }
+    Ok(Value::Object(root))
+}
+
+#[cfg(feature = "testing")]
+fn touch(path: PathBuf) -> Result<()> {
+    let file = File::options()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("unable to touch {:?}", &path))?;
+    file.set_modified(SystemTime::now())?;
+    Ok(())
+}
+
+fn dir_size(path: impl AsRef<Path>) -> Result<u64> {
+    let mut total = 0;
+    for entry in std::fs::read_dir(path)? {
+        let entry = entry?;
+        let meta = std::fs::symlink_metadata(entry.path())?;
+        if meta.is_dir() {
+            total += dir_size(entry.path())?;
+        } else if meta.is_file() {
+            total += meta.len();
+        }
+    }
+    Ok(total)
 }
 
 fn ensure_directory_exists(path: impl AsRef<Path>) -> Result<PathBuf> {
@@ -174,20 +1221,63 @@ fn ensure_directory_exists(path: impl AsRef<Path>) -> Result<PathBuf> {
         Ok(()) => {}
         Err(e) => match e.kind() {
             std::io::ErrorKind::AlreadyExists => {}
-            _ => return Err(e.into()),
+            _ => {
+                return Err(anyhow::Error::new(e)
+                    .context(format!("unable to create directory {:?}", path.as_ref())))
+            }
         },
     };
-    return Ok(path.as_ref().canonicalize().unwrap());
+    return Ok(path.as_ref().canonicalize()?);
+}
+
+/// The parameter directory `ROOT/PARAM_ID`, without creating it.
+fn param_dir_path<T: Experiment>(exp: &T) -> PathBuf {
+    let mut dir = resolve_root_dir::<T>();
+    dir.push(exp.parameter().id_str());
+    dir
+}
+
+/// The directory of [`Experiment::output_dir`], without creating it.
+fn output_dir_path<T: Experiment>(exp: &T) -> PathBuf {
+    let mut dir = param_dir_path(exp);
+    if T::nested_input_dirs() {
+        dir.push(exp.input().id_str());
+    }
+    dir
 }
 
 /// Has a filename-friendly string ID.
 pub trait IdStr: Serialize {
+    /// A short human-readable label, prepended to the hash by the default [`IdStr::id_str`] to make directories
+    /// easier to navigate, eg `myparams-Ab3x...`.  Default is none.
+    fn id_label(&self) -> Option<String> {
+        None
+    }
+
+    /// The hash algorithm used by the default [`IdStr::id_str`], and recorded in index files for parameters.
+    /// Implementors of [`IdStr::id_str`] should override this if they use a different algorithm.  WARNING: changing
+    /// the algorithm changes every ID, and hence every parameter directory; index files written with a different
+    /// algorithm can no longer be loaded.  Default is [`IdHashAlgo::Sha224`].
+    fn id_hash_algo() -> IdHashAlgo {
+        IdHashAlgo::Sha224
+    }
+
     fn id_str(&self) -> String {
-        id_from_serialised(self)
+        with_id_label(self, id_from_serialised_with(self, Self::id_hash_algo()))
+    }
+}
+
+/// Prepend the [`IdStr::id_label`] of `val` to `id`, if it has one, as the default [`IdStr::id_str`] does.  Useful
+/// when implementing [`IdStr::id_str`] with a different hash.
+pub fn with_id_label<T: IdStr + ?Sized>(val: &T, id: String) -> String {
+    match val.id_label() {
+        Some(label) => format!("{}-{}", sanitize_job_name(&label), id),
+        None => id,
     }
 }
 
 /// An amount of memory for Slurm, rounded to mebibytes (2**20 bytes)
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct MemoryAmount(usize);
 
 impl MemoryAmount {
@@ -199,21 +1289,157 @@ impl MemoryAmount {
         MemoryAmount(amount)
     }
 
+    /// Note that despite the name, this uses 1024-based units (the same as [`MemoryAmount::from_gib`]), matching
+    /// Slurm's interpretation of `G`.
     pub fn from_gb(amount: usize) -> Self {
         MemoryAmount(amount * 1024)
     }
 
+    /// Like [`MemoryAmount::from_gb`], rounded to the nearest mebibyte.
     pub fn from_gb_f64(amount: f64) -> Self {
         MemoryAmount((amount * 1024.0).round() as usize)
     }
 
+    /// An amount in mebibytes (2**20 bytes).  Equivalent to [`MemoryAmount::from_mb`].
+    pub fn from_mib(amount: usize) -> Self {
+        MemoryAmount(amount)
+    }
+
+    /// An amount in gibibytes (2**30 bytes).  Equivalent to [`MemoryAmount::from_gb`].
+    pub fn from_gib(amount: usize) -> Self {
+        MemoryAmount(amount * 1024)
+    }
+
+    /// An amount in gibibytes, rounded to the nearest mebibyte.  Equivalent to [`MemoryAmount::from_gb_f64`].
+    pub fn from_gib_f64(amount: f64) -> Self {
+        MemoryAmount((amount * 1024.0).round() as usize)
+    }
+
     pub fn as_mb(&self) -> usize {
         self.0
     }
+
+    /// The amount in mebibytes.  Equivalent to [`MemoryAmount::as_mb`].
+    pub fn as_mib(&self) -> usize {
+        self.0
+    }
+
+    /// Parse a memory amount in Slurm's syntax: an integer with an optional `K`, `M`, `G` or `T` suffix, where
+    /// no suffix means megabytes, eg `4G` or `512`.  Units are powers of 1024.  Kilobyte amounts are rounded up to
+    /// the nearest mebibyte.
+    pub fn from_str_slurm(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let (digits, unit) = match s.char_indices().last() {
+            Some((i, c)) if c.is_ascii_alphabetic() => (&s[..i], Some(c.to_ascii_uppercase())),
+            _ => (s, None),
+        };
+        let amount: usize = digits
+            .parse()
+            .with_context(|| format!("invalid memory amount `{}`", s))?;
+        let mb = match unit {
+            None | Some('M') => Some(amount),
+            Some('K') => Some(amount.div_ceil(1024)),
+            Some('G') => amount.checked_mul(1024),
+            Some('T') => amount.checked_mul(1024 * 1024),
+            Some(c) => anyhow::bail!("invalid memory unit `{}` in `{}`", c, s),
+        };
+        let mb = mb.with_context(|| format!("memory amount `{}` is too large", s))?;
+        Ok(MemoryAmount(mb))
+    }
+
+    /// A fraction of this amount, rounded down to the nearest mebibyte.
+    pub fn fraction(&self, fraction: f64) -> Self {
+        MemoryAmount((self.0 as f64 * fraction).floor() as usize)
+    }
+}
+
+impl FromStr for MemoryAmount {
+    type Err = anyhow::Error;
+
+    /// Parse a memory amount such as `4GB`, `1.5gb` or `512`.  The suffixes `MB`, `GB` and `TB` are case-insensitive
+    /// and may follow a decimal number; units match [`MemoryAmount::from_gb`], and a bare integer is megabytes.
+    /// Fractional amounts are rounded up to the nearest megabyte.  Anything else is parsed with
+    /// [`MemoryAmount::from_str_slurm`].
+    fn from_str(s: &str) -> Result<Self> {
+        let t = s.trim();
+        let upper = t.to_ascii_uppercase();
+        let scale = if upper.ends_with("MB") {
+            1.0
+        } else if upper.ends_with("GB") {
+            1024.0
+        } else if upper.ends_with("TB") {
+            1024.0 * 1024.0
+        } else {
+            return MemoryAmount::from_str_slurm(s);
+        };
+        let amount: f64 = t[..t.len() - 2].trim().parse().with_context(|| {
+            format!(
+                "invalid memory amount `{}`, expected eg `4GB` or `512MB`",
+                s
+            )
+        })?;
+        if !amount.is_finite() || amount < 0.0 {
+            anyhow::bail!("invalid memory amount `{}`", s)
+        }
+        Ok(MemoryAmount((amount * scale).ceil() as usize))
+    }
+}
+
+/// A time limit built from days, hours, minutes and seconds, eg `SlurmTime::hours(2).plus_minutes(30)`.  Converts
+/// into a [`Duration`] for [`ResourcePolicy::time`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct SlurmTime(u64);
+
+impl SlurmTime {
+    pub fn seconds(secs: u64) -> Self {
+        SlurmTime(secs)
+    }
+
+    pub fn minutes(mins: u64) -> Self {
+        SlurmTime(mins * 60)
+    }
+
+    pub fn hours(hrs: u64) -> Self {
+        SlurmTime(hrs * 3600)
+    }
+
+    pub fn days(days: u64) -> Self {
+        SlurmTime(days * 86400)
+    }
+
+    pub fn plus_seconds(self, secs: u64) -> Self {
+        SlurmTime(self.0 + secs)
+    }
+
+    pub fn plus_minutes(self, mins: u64) -> Self {
+        SlurmTime(self.0 + mins * 60)
+    }
+
+    pub fn plus_hours(self, hrs: u64) -> Self {
+        SlurmTime(self.0 + hrs * 3600)
+    }
+
+    pub fn as_secs(&self) -> u64 {
+        self.0
+    }
+}
+
+impl From<SlurmTime> for Duration {
+    fn from(t: SlurmTime) -> Self {
+        Duration::from_secs(t.0)
+    }
+}
+
+impl Display for SlurmTime {
+    /// Formats as with [`fmt_as_slurm_time`]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&fmt_as_slurm_time(self.0))
+    }
 }
 
 /// Slurm email notification events. See the `--mail-type` parameter to [`sbatch`](https://slurm.schedmd.com/sbatch.html)
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Serialize, Deserialize, Hash)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum MailType {
     None,
@@ -231,6 +1457,19 @@ pub enum MailType {
     ArrayTasks,
 }
 
+impl MailType {
+    /// The individual job state notifications: [`MailType::Begin`], [`MailType::End`], [`MailType::Fail`] and
+    /// [`MailType::Requeue`].
+    pub fn all_individual() -> Vec<MailType> {
+        vec![
+            MailType::Begin,
+            MailType::End,
+            MailType::Fail,
+            MailType::Requeue,
+        ]
+    }
+}
+
 impl Display for MailType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         use MailType::*;
@@ -254,6 +1493,7 @@ impl Display for MailType {
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
 pub struct SlurmResources {
     #[serde(rename = "script")]
     pub script: String,
@@ -267,10 +1507,26 @@ pub struct SlurmResources {
     pub cpus: usize,
     #[serde(rename = "nodes")]
     pub nodes: usize,
+    #[serde(rename = "ntasks", skip_serializing_if = "Option::is_none")]
+    pub ntasks: Option<usize>,
+    #[serde(rename = "ntasks-per-node", skip_serializing_if = "Option::is_none")]
+    pub ntasks_per_node: Option<usize>,
+    #[serde(rename = "gpus", skip_serializing_if = "Option::is_none")]
+    pub gpus: Option<usize>,
+    #[serde(rename = "gpus-per-node", skip_serializing_if = "Option::is_none")]
+    pub gpus_per_node: Option<usize>,
+    #[serde(rename = "gpus-per-task", skip_serializing_if = "Option::is_none")]
+    pub gpus_per_task: Option<usize>,
     #[serde(rename = "time")]
     pub time: String,
-    #[serde(rename = "mem")]
-    pub memory: String,
+    #[serde(rename = "time-min", skip_serializing_if = "Option::is_none")]
+    pub time_min: Option<String>,
+    #[serde(rename = "mem", skip_serializing_if = "Option::is_none")]
+    pub memory: Option<String>,
+    #[serde(rename = "mem-per-gpu", skip_serializing_if = "Option::is_none")]
+    pub mem_per_gpu: Option<String>,
+    #[serde(rename = "mem-per-cpu", skip_serializing_if = "Option::is_none")]
+    pub mem_per_cpu: Option<String>,
     #[serde(rename = "mail-user", skip_serializing_if = "Option::is_none")]
     pub mail_user: Option<String>,
     #[serde(rename = "mail-type", skip_serializing_if = "Option::is_none")]
@@ -281,9 +1537,74 @@ pub struct SlurmResources {
     pub exclude: Option<String>,
     #[serde(rename = "nodelist", skip_serializing_if = "Option::is_none")]
     pub nodelist: Option<String>,
+    #[serde(
+        rename = "oversubscribe",
+        skip_serializing_if = "std::ops::Not::not",
+        default
+    )]
+    pub oversubscribe: bool,
+    #[serde(rename = "nice", skip_serializing_if = "Option::is_none")]
+    pub nice: Option<i32>,
+    #[serde(rename = "container-image", skip_serializing_if = "Option::is_none")]
+    pub container_image: Option<String>,
+    #[serde(rename = "container-mounts", skip_serializing_if = "Option::is_none")]
+    pub container_mounts: Option<String>,
+    #[serde(rename = "container-workdir", skip_serializing_if = "Option::is_none")]
+    pub container_workdir: Option<String>,
+    #[serde(rename = "cpu-bind", skip_serializing_if = "Option::is_none")]
+    pub cpu_bind: Option<String>,
+    #[serde(rename = "array", skip_serializing_if = "Option::is_none")]
+    pub array: Option<String>,
+    #[serde(rename = "account", skip_serializing_if = "Option::is_none")]
+    pub account: Option<String>,
+    #[serde(rename = "qos", skip_serializing_if = "Option::is_none")]
+    pub qos: Option<String>,
+    #[serde(rename = "partition", skip_serializing_if = "Option::is_none")]
+    pub partition: Option<String>,
+    #[serde(rename = "begin", skip_serializing_if = "Option::is_none")]
+    pub begin: Option<String>,
+    #[serde(rename = "wckey", skip_serializing_if = "Option::is_none")]
+    pub wckey: Option<String>,
+    #[serde(rename = "propagate", skip_serializing_if = "Option::is_none")]
+    pub propagate: Option<String>,
+    #[serde(
+        rename = "kill-on-invalid-dep",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub kill_on_invalid_dep: Option<String>,
+    /// The command (without `argv[0]`) these resources were computed from, set by the pipe server.  Not an
+    /// `sbatch` option.
+    #[serde(rename = "argv", skip_serializing_if = "Option::is_none")]
+    pub argv: Option<Vec<String>>,
 }
 
-fn fmt_as_slurm_time(mut secs: u64) -> String {
+/// Insert `prolog` after the `#!` line of `script` (or at the start if there is none) and append `epilog`.
+fn wrap_script(script: String, prolog: Option<String>, epilog: Option<String>) -> String {
+    if prolog.is_none() && epilog.is_none() {
+        return script;
+    }
+    let (shebang, body) = match script.split_once('\n') {
+        Some((first, rest)) if first.starts_with("#!") => (Some(first), rest),
+        _ => (None, script.as_str()),
+    };
+    let mut wrapped = String::with_capacity(script.len());
+    for part in [shebang, prolog.as_deref(), Some(body), epilog.as_deref()]
+        .into_iter()
+        .flatten()
+        .filter(|p| !p.is_empty())
+    {
+        wrapped.push_str(part);
+        if !part.ends_with('\n') {
+            wrapped.push('\n');
+        }
+    }
+    wrapped
+}
+
+/// Format a duration in seconds as a Slurm time limit, `D-H:MM:SS`.  Durations under a minute are rounded up to one
+/// minute, since some schedulers reject a zero time limit.
+pub fn fmt_as_slurm_time(secs: u64) -> String {
+    let mut secs = secs.max(60);
     let mut minutes = secs / 60;
     secs -= minutes * 60;
     let mut hrs = minutes / 60;
@@ -293,8 +1614,106 @@ fn fmt_as_slurm_time(mut secs: u64) -> String {
     format!("{}-{}:{:02}:{:02}", days, hrs, minutes, secs)
 }
 
+/// Parse a time limit, either in one of Slurm's formats (`MM`, `MM:SS`, `HH:MM:SS`, `D-HH`, `D-HH:MM` or
+/// `D-HH:MM:SS`) or as a [`humantime`] duration such as `2h30m`.  The inverse of [`fmt_as_slurm_time`] for
+/// durations of at least a minute.
+pub fn parse_slurm_time(s: &str) -> Result<Duration> {
+    let s = s.trim();
+    if s.is_empty()
+        || !s
+            .chars()
+            .all(|c| c.is_ascii_digit() || c == ':' || c == '-')
+    {
+        return humantime::parse_duration(s).with_context(|| format!("invalid time limit `{}`", s));
+    }
+    let invalid = || anyhow::anyhow!("invalid time limit `{}`", s);
+    let parse = |x: &str| x.parse::<u64>().map_err(|_| invalid());
+
+    let (days, rest) = match s.split_once('-') {
+        Some((d, rest)) => (Some(parse(d)?), rest),
+        None => (None, s),
+    };
+    let fields = rest.split(':').map(parse).collect::<Result<Vec<_>>>()?;
+    let (h, m, sec) = match (days, fields.as_slice()) {
+        (None, &[m]) => (0, m, 0),
+        (None, &[m, sec]) => (0, m, sec),
+        (None, &[h, m, sec]) | (Some(_), &[h, m, sec]) => (h, m, sec),
+        (Some(_), &[h]) => (h, 0, 0),
+        (Some(_), &[h, m]) => (h, m, 0),
+        _ => return Err(invalid()),
+    };
+    let secs = ((days.unwrap_or(0) * 24 + h) * 60 + m) * 60 + sec;
+    Ok(Duration::from_secs(secs))
+}
+
+/// Make a job name safe for `squeue` and downstream parsing, by replacing every character other than ASCII
+/// alphanumerics, `-`, `_` and `.` with `_`.
+pub fn sanitize_job_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "-_.".contains(c) {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Feature names referenced in a Slurm constraint expression such as `intel&(gpu|[rack1|rack2]*2)`.
+pub fn constraint_features(expr: &str) -> Vec<&str> {
+    expr.split(|c: char| "&|,[]()*".contains(c))
+        .map(str::trim)
+        .filter(|f| !f.is_empty() && f.parse::<u32>().is_err())
+        .collect()
+}
+
+/// Check that every feature referenced in a constraint expression is in `known`.
+pub fn validate_constraint(expr: &str, known: &[String]) -> Result<()> {
+    let unknown: Vec<_> = constraint_features(expr)
+        .into_iter()
+        .filter(|f| !known.iter().any(|k| k == f))
+        .collect();
+    if !unknown.is_empty() {
+        anyhow::bail!(
+            "unknown features in constraint `{}`: {}",
+            expr,
+            unknown.join(", ")
+        )
+    }
+    Ok(())
+}
+
 impl SlurmResources {
-    pub fn new(exp: &impl ResourcePolicy) -> Self {
+    /// Check that at most one memory specification (`mem`, `mem-per-gpu` or `mem-per-cpu`) is set.
+    pub fn validate(&self) -> Result<()> {
+        let specs: Vec<_> = [
+            ("mem", &self.memory),
+            ("mem-per-gpu", &self.mem_per_gpu),
+            ("mem-per-cpu", &self.mem_per_cpu),
+        ]
+        .into_iter()
+        .filter(|(_, spec)| spec.is_some())
+        .map(|(name, _)| name)
+        .collect();
+        if specs.len() > 1 {
+            anyhow::bail!("conflicting memory specifications: {}", specs.join(", "))
+        }
+        Ok(())
+    }
+
+    /// The JSON schema of the Slurm info output, for validating the output of `--slurminfo` and the
+    /// pipe server.  Requires the `schemars` feature.
+    #[cfg(feature = "schemars")]
+    pub fn json_schema() -> schemars::schema::RootSchema {
+        schemars::schema_for!(SlurmResources)
+    }
+
+    pub fn new(exp: &impl ResourcePolicy) -> Result<Self> {
+        Self::with_overrides(exp, &ResourceOverrides::default())
+    }
+
+    fn with_overrides(exp: &impl ResourcePolicy, overrides: &ResourceOverrides) -> Result<Self> {
         let mail_type = {
             let mt = exp.mail_type();
             if mt.is_empty() {
@@ -304,22 +1723,125 @@ impl SlurmResources {
             }
         };
 
-        SlurmResources {
+        let prefix = overrides
+            .jobname_prefix
+            .clone()
+            .or_else(|| exp.job_name_prefix());
+        let job_name = exp.job_name().map(|n| match prefix {
+            Some(prefix) => format!("{}{}", prefix, n),
+            None => n,
+        });
+        let memory = match exp.memory_fraction() {
+            Some(fraction) => {
+                node_memory_fraction(overrides.node_mem.or_else(|| exp.node_memory()), fraction)?
+            }
+            None => exp.memory(),
+        };
+        let mut resources = SlurmResources {
             time: fmt_as_slurm_time(exp.time().as_secs()),
-            memory: format!("{}MB", exp.memory().as_mb()),
-            script: exp.script(),
-            log_err: Some(exp.log_err()),
-            log_out: exp.log_out(),
-            job_name: exp.job_name(),
+            time_min: exp.time_min().map(|t| fmt_as_slurm_time(t.as_secs())),
+            memory: match (exp.mem_per_gpu(), exp.mem_per_cpu()) {
+                (None, None) => Some(format!("{}MB", memory.as_mb())),
+                _ => None,
+            },
+            mem_per_gpu: exp.mem_per_gpu().map(|m| format!("{}MB", m.as_mb())),
+            mem_per_cpu: exp.mem_per_cpu().map(|m| format!("{}MB", m.as_mb())),
+            script: wrap_script(exp.script(), exp.prolog(), exp.epilog()),
+            log_err: Some(exp.log_err()?),
+            log_out: exp.log_out()?,
+            job_name: if exp.sanitize_job_names() {
+                job_name.map(|n| sanitize_job_name(&n))
+            } else {
+                job_name
+            },
             mail_user: exp.mail_user(),
             constraint: exp.constraint(),
             mail_type,
             cpus: exp.cpus(),
             nodes: exp.nodes(),
+            ntasks: exp.ntasks(),
+            ntasks_per_node: exp.ntasks_per_node(),
+            gpus: exp.gpus(),
+            gpus_per_node: exp.gpus_per_node(),
+            gpus_per_task: exp.gpus_per_task(),
             nodelist: exp.nodelist(),
+            oversubscribe: exp.oversubscribe(),
+            nice: exp.nice(),
+            container_image: exp.container_image(),
+            container_mounts: exp.container_mounts(),
+            container_workdir: exp.container_workdir(),
+            cpu_bind: exp.cpu_bind(),
+            array: exp.array(),
+            account: exp.account(),
+            qos: exp.qos(),
+            partition: exp.partition(),
             exclude: exp.exclude(),
+            begin: exp
+                .begin()
+                .map(|offset| format!("now+{}", offset.as_secs()))
+                .or_else(|| exp.begin_raw()),
+            wckey: exp.wckey(),
+            propagate: exp.propagate(),
+            kill_on_invalid_dep: exp
+                .kill_on_invalid_dep()
+                .map(|kill| if kill { "yes" } else { "no" }.to_string()),
+            argv: None,
+        };
+        exp.apply_profile(&mut resources);
+        Ok(resources)
+    }
+
+    /// Like [`SlurmResources::new`], but also validates the resources (see [`SlurmResources::validate`]).  The
+    /// minimum time limit must not exceed the time limit, and the constraint is checked against
+    /// [`ResourcePolicy::known_features`].
+    pub fn try_new(exp: &impl ResourcePolicy) -> Result<Self> {
+        Self::try_with_overrides(exp, &ResourceOverrides::default())
+    }
+
+    fn try_with_overrides(
+        exp: &impl ResourcePolicy,
+        overrides: &ResourceOverrides,
+    ) -> Result<Self> {
+        let resources = Self::with_overrides(exp, overrides)?;
+        resources.validate()?;
+        if let Some(time_min) = exp.time_min() {
+            if time_min > exp.time() {
+                anyhow::bail!(
+                    "minimum time limit ({:?}) exceeds time limit ({:?})",
+                    time_min,
+                    exp.time()
+                )
+            }
+        }
+        if let (Some(constraint), Some(known)) = (&resources.constraint, exp.known_features()) {
+            validate_constraint(constraint, &known)?;
         }
+        Ok(resources)
+    }
+
+    /// Compute the resources for many experiments in-process, validating each with [`SlurmResources::try_new`].
+    /// This is the library counterpart of the pipe server.
+    pub fn for_all<T: ResourcePolicy>(exps: &[T]) -> Result<Vec<Self>> {
+        exps.iter().map(Self::try_new).collect()
+    }
+}
+
+/// Resource settings given on the command line, which take precedence over those of the [`ResourcePolicy`].
+#[derive(Debug, Clone, Default)]
+struct ResourceOverrides {
+    /// See `--jobname-prefix`
+    jobname_prefix: Option<String>,
+    /// See `--node-mem`
+    node_mem: Option<MemoryAmount>,
+}
+
+/// A fraction of the node memory, see [`ResourcePolicy::memory_fraction`].
+fn node_memory_fraction(node_mem: Option<MemoryAmount>, fraction: f64) -> Result<MemoryAmount> {
+    if !(fraction > 0.0 && fraction <= 1.0) {
+        anyhow::bail!("memory fraction must be in (0, 1], got {}", fraction)
     }
+    let node_mem = node_mem.ok_or_else(|| anyhow::anyhow!("node memory is unknown"))?;
+    Ok(node_mem.fraction(fraction))
 }
 
 /// For running with `slurm-harray`, your main experiment should implement this trait,
@@ -328,12 +1850,57 @@ pub trait ResourcePolicy: Experiment {
     /// The Slurm script loaded as a string.
     fn script(&self) -> String;
 
+    /// Setup commands (eg `module load ...`) run before [`ResourcePolicy::script`].  They are inserted after the
+    /// script's `#!` line, if it has one.
+    fn prolog(&self) -> Option<String> {
+        None
+    }
+
+    /// Teardown commands (eg scratch cleanup) appended after [`ResourcePolicy::script`].
+    fn epilog(&self) -> Option<String> {
+        None
+    }
+
     /// Time limit for this job
     fn time(&self) -> Duration;
 
+    /// Minimum time limit, allowing the job to start sooner if less time is available (`sbatch --time-min`).
+    /// Must not exceed [`ResourcePolicy::time`].
+    fn time_min(&self) -> Option<Duration> {
+        None
+    }
+
     /// Maximum amount of memory allocated to this job
     fn memory(&self) -> MemoryAmount;
 
+    /// Request a fraction of the node's memory instead of [`ResourcePolicy::memory`], eg `0.5` for half of it.  The
+    /// node memory is the value of the `--node-mem` argument, falling back to [`ResourcePolicy::node_memory`].
+    /// Computing the resources fails if the node memory is unknown or the fraction is not in `(0, 1]`.  Default is
+    /// none.
+    fn memory_fraction(&self) -> Option<f64> {
+        None
+    }
+
+    /// Total memory of the compute node this job runs on, if known.  Used by [`ResourcePolicy::memory_fraction`]
+    /// when `--node-mem` isn't given.  The default is the value of
+    /// the `LABRAT_NODE_MEM` environment variable (ignored if it can't be parsed, see [`MemoryAmount::from_str`]).
+    fn node_memory(&self) -> Option<MemoryAmount> {
+        std::env::var("LABRAT_NODE_MEM")
+            .ok()
+            .and_then(|s| s.parse().ok())
+    }
+
+    /// A fraction of [`ResourcePolicy::node_memory`], eg `0.5` for half of the node's memory.  Fails if the node
+    /// memory is unknown or `fraction` is not in `(0, 1]`.  Unlike [`ResourcePolicy::memory_fraction`], this ignores
+    /// `--node-mem`.
+    #[deprecated(
+        since = "2.0.0",
+        note = "override `ResourcePolicy::memory_fraction` instead, which also takes `--node-mem` into account"
+    )]
+    fn node_memory_fraction(&self, fraction: f64) -> Result<MemoryAmount> {
+        node_memory_fraction(self.node_memory(), fraction)
+    }
+
     /// Number of CPUs
     fn cpus(&self) -> usize {
         1
@@ -344,11 +1911,59 @@ pub trait ResourcePolicy: Experiment {
         1
     }
 
+    /// Number of tasks, eg MPI ranks (`sbatch --ntasks`).  The default is none, leaving it to Slurm: one task, or
+    /// `n` per node if [`ResourcePolicy::ntasks_per_node`] is `Some(n)`.
+    fn ntasks(&self) -> Option<usize> {
+        None
+    }
+
+    /// Number of tasks per node (`sbatch --ntasks-per-node`)
+    fn ntasks_per_node(&self) -> Option<usize> {
+        None
+    }
+
+    /// Memory per allocated GPU (`sbatch --mem-per-gpu`).  If set, [`ResourcePolicy::memory`] is ignored.
+    fn mem_per_gpu(&self) -> Option<MemoryAmount> {
+        None
+    }
+
+    /// Memory per allocated CPU (`sbatch --mem-per-cpu`).  If set, [`ResourcePolicy::memory`] is ignored.  May not
+    /// be combined with [`ResourcePolicy::mem_per_gpu`].
+    fn mem_per_cpu(&self) -> Option<MemoryAmount> {
+        None
+    }
+
+    /// Total number of GPUs (`sbatch --gpus`)
+    fn gpus(&self) -> Option<usize> {
+        None
+    }
+
+    /// Number of GPUs per node (`sbatch --gpus-per-node`)
+    fn gpus_per_node(&self) -> Option<usize> {
+        None
+    }
+
+    /// Number of GPUs per task (`sbatch --gpus-per-task`)
+    fn gpus_per_task(&self) -> Option<usize> {
+        None
+    }
+
     /// The job name.  The default is the parameter ID-string.
     fn job_name(&self) -> Option<String> {
         Some(self.parameter().id_str())
     }
 
+    /// A prefix prepended to [`ResourcePolicy::job_name`], so that a project's jobs are easy to pick out in `squeue`.
+    /// The default is none.  A prefix given with `--jobname-prefix` takes precedence.
+    fn job_name_prefix(&self) -> Option<String> {
+        None
+    }
+
+    /// Whether [`ResourcePolicy::job_name`] is passed through [`sanitize_job_name`].  Default is `true`.
+    fn sanitize_job_names(&self) -> bool {
+        true
+    }
+
     /// Email to send notifications to
     fn mail_user(&self) -> Option<String> {
         None
@@ -364,46 +1979,237 @@ pub trait ResourcePolicy: Experiment {
         None
     }
 
+    /// Node features available on the cluster (see `sinfo -o %f`).  If provided, features referenced by
+    /// [`ResourcePolicy::constraint`] are validated against this list.
+    fn known_features(&self) -> Option<Vec<String>> {
+        None
+    }
+
     /// Exclude certain nodes (`sbatch --exclude`)
     fn exclude(&self) -> Option<String> {
         None
     }
 
+    /// Partition to submit the job to (`sbatch --partition`)
+    fn partition(&self) -> Option<String> {
+        None
+    }
+
+    /// Quality of service for the job (`sbatch --qos`)
+    fn qos(&self) -> Option<String> {
+        None
+    }
+
+    /// Account to charge the job to (`sbatch --account`)
+    fn account(&self) -> Option<String> {
+        None
+    }
+
+    /// CPU binding for tasks, eg `cores` (`srun --cpu-bind`)
+    fn cpu_bind(&self) -> Option<String> {
+        None
+    }
+
+    /// Working directory inside the container (Pyxis `--container-workdir`)
+    fn container_workdir(&self) -> Option<String> {
+        None
+    }
+
+    /// Mounts for the container, eg `/data:/data,/scratch:/scratch` (Pyxis `--container-mounts`)
+    fn container_mounts(&self) -> Option<String> {
+        None
+    }
+
+    /// Container image to run the job in, eg `nvcr.io#nvidia/pytorch:23.10-py3` (Pyxis `--container-image`)
+    fn container_image(&self) -> Option<String> {
+        None
+    }
+
+    /// Allow the job's resources to be shared with other jobs (`sbatch --oversubscribe`).  Default is `false`.
+    fn oversubscribe(&self) -> bool {
+        false
+    }
+
+    /// Adjust the job's scheduling priority; positive values lower the priority (`sbatch --nice`)
+    fn nice(&self) -> Option<i32> {
+        None
+    }
+
     /// Specify a nodelist (`sbatch --nodelist`)
     fn nodelist(&self) -> Option<String> {
         None
     }
 
+    /// Defer the start of this job by an offset from the time it is submitted (`sbatch --begin=now+SECONDS`), rounded
+    /// down to the second.  Takes precedence over [`ResourcePolicy::begin_raw`].
+    fn begin(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Defer the start of this job using a raw Slurm time specification, eg `16:00` or `now+1hour` (`sbatch --begin`)
+    fn begin_raw(&self) -> Option<String> {
+        None
+    }
+
+    /// Workload characterization key, used for accounting (`sbatch --wckey`)
+    fn wckey(&self) -> Option<String> {
+        None
+    }
+
+    /// Which resource limits to propagate to the compute nodes, eg `STACK` or `NONE` (`sbatch --propagate`)
+    fn propagate(&self) -> Option<String> {
+        None
+    }
+
+    /// Whether Slurm should cancel this job if its dependencies can never be satisfied (`sbatch --kill-on-invalid-dep`)
+    fn kill_on_invalid_dep(&self) -> Option<bool> {
+        None
+    }
+
+    /// Submit as a job array with the given index specification, eg `0-99%10` (`sbatch --array`).  All tasks in
+    /// the array share the same [`ResourcePolicy::job_name`]; tasks are distinguished by `SLURM_ARRAY_TASK_ID`.
+    /// When set, the default log paths include `%A_%a` so each task writes its own logs.
+    fn array(&self) -> Option<String> {
+        None
+    }
+
     /// Path to place STDERR log. Should be an absolute path.  [`Experiment::get_output_path`] or
     /// [`Experiment::get_output_path_prefixed`] may be helpful.
-    fn log_err(&self) -> PathBuf {
-        self.get_output_path_prefixed(".err")
+    fn log_err(&self) -> Result<PathBuf> {
+        if self.array().is_some() {
+            self.get_output_path_prefixed("-%A_%a.err")
+        } else {
+            self.get_output_path_prefixed(".err")
+        }
     }
 
     /// Path to place STDERR log. Should be an absolute path.  [`Experiment::get_output_path`] or
     /// [`Experiment::get_output_path_prefixed`] may be helpful.
-    fn log_out(&self) -> PathBuf {
-        self.get_output_path_prefixed(".out")
+    fn log_out(&self) -> Result<PathBuf> {
+        if self.array().is_some() {
+            self.get_output_path_prefixed("-%A_%a.out")
+        } else {
+            self.get_output_path_prefixed(".out")
+        }
+    }
+
+    /// A compact JSON summary of this experiment (IDs, requested resources and completion status), for building
+    /// results overviews.
+    fn summary(&self) -> Result<serde_json::Value> {
+        let resources = SlurmResources::new(self)?;
+        Ok(serde_json::json!({
+            "param_id": self.parameter().id_str(),
+            "input_id": self.input().id_str(),
+            "job_name": resources.job_name,
+            "cpus": resources.cpus,
+            "memory": resources.memory,
+            "time": resources.time,
+            "complete": self.status() == Status::Complete,
+        }))
+    }
+
+    /// A hook for adjusting the computed resources based on [`Experiment::profile`].  The default implementation
+    /// requests a single CPU for every profile other than [`Profile::Default`], and also disables email
+    /// notifications for [`Profile::Bench`].
+    fn apply_profile(&self, resources: &mut SlurmResources) {
+        if self.profile() != Profile::Default {
+            resources.cpus = 1;
+        }
+        if self.profile() == Profile::Bench {
+            resources.mail_user = None;
+            resources.mail_type = None;
+        }
+    }
+
+    /// Write the index file to the output directory (see [`Experiment::write_index_file`]), recording the
+    /// computed Slurm resources under the `"slurm"` key.  Only JSON and YAML index files can record resources; for
+    /// other formats (see [`Experiment::ser_format`]) an error is returned.
+    fn write_index_file_with_slurm(&self) -> Result<()> {
+        write_index(self, Some(SlurmResources::new(self)?))
+    }
+
+    /// Instantiate an experiment from disk (see [`Experiment::from_index_file`]) along with the Slurm resources
+    /// it requested.  If the index file was written by [`ResourcePolicy::write_index_file_with_slurm`], the
+    /// recorded resources are returned.  Otherwise they are recomputed; since the index file records the profile,
+    /// these match the original submission as long as they don't depend on the config.
+    fn from_index_file_with_slurm(
+        path: impl AsRef<Path> + Debug,
+    ) -> Result<(Self, SlurmResources)> {
+        let (exp, meta) = read_index::<Self>(path)?;
+        let resources = match meta.slurm {
+            Some(resources) => resources,
+            None => SlurmResources::new(&exp)?,
+        };
+        Ok((exp, resources))
     }
 
     /// Parse command-line arguments for inputs, parameters and config, before handling
     /// and Slurm-related arguments.  May exit the program.
     fn from_cl_args_with_slurm() -> Result<Self> {
-        if let Some((read_fd, write_fd)) = check_args_for_slurm_pipe()? {
-            run_pipe_server::<Self>(&read_fd, &write_fd)?;
-            exit(0)
+        exit_on_cl_outcome(Self::try_from_args_with_slurm(std::env::args_os()))
+    }
+
+    /// Like [`ResourcePolicy::from_cl_args_with_slurm`], but parses the given command-line arguments (including
+    /// `argv[0]`) and never exits the program.
+    fn try_from_args_with_slurm<I, A>(args: I) -> Result<ClOutcome<Self>>
+    where
+        I: IntoIterator<Item = A>,
+        A: Into<OsString> + Clone,
+    {
+        let args: Vec<OsString> = args.into_iter().map(Into::into).collect();
+        if let Some(pipe) = check_args_for_slurm_pipe(&args)? {
+            let timing = if pipe.looping {
+                run_pipe_server_loop::<Self>(&pipe.read, &pipe.write)?
+            } else {
+                run_pipe_server::<Self>(&pipe.read, &pipe.write)?
+            };
+            if pipe.timing {
+                timing.report();
+            }
+            return Ok(ClOutcome::Exit);
+        }
+
+        if let Some(path) = check_args_for_flag_value(&args, "--p-parallel")? {
+            let commands: Vec<Vec<String>> = read_file(&path)?;
+            for line in parallel_command_lines(&commands) {
+                println!("{}", line);
+            }
+            return Ok(ClOutcome::Exit);
+        }
+
+        if ClArgs::<SlurmArgs, Self>::run_pre_parse_tools(&args)? {
+            return Ok(ClOutcome::Exit);
+        }
+
+        let args = ClArgs::<SlurmArgs, Self>::try_parse_checked(args)?;
+
+        #[cfg(feature = "schemars")]
+        if args.slurm.schema {
+            serde_json::to_writer_pretty(stdout(), &SlurmResources::json_schema())?;
+            return Ok(ClOutcome::Exit);
         }
 
-        let args = ClArgs::<SlurmArgs, Self>::parse();
         let slurm_info = args.slurm.info;
+        let tools = args.tools.clone();
+        let overwrite_outputs = args.overwrite_outputs;
+        let overrides = args.resource_overrides();
         let exp = args.into_experiment()?;
 
         if slurm_info {
-            serde_json::to_writer_pretty(stdout(), &SlurmResources::new(&exp))?;
-            exit(0);
+            let resources = SlurmResources::try_with_overrides(&exp, &overrides)?;
+            serde_json::to_writer_pretty(stdout(), &resources)?;
+            return Ok(ClOutcome::Exit);
         }
 
-        Ok(exp)
+        if tools.run(&exp)? {
+            return Ok(ClOutcome::Exit);
+        }
+
+        if Self::protect_outputs() && !overwrite_outputs {
+            exp.check_outputs_absent()?;
+        }
+
+        Ok(ClOutcome::Experiment(exp))
     }
 }
 
@@ -419,14 +2225,183 @@ struct SlurmArgs {
         group("slurm-managed"),
     )]
     pipe: Option<Vec<String>>,
+    /// Like --p-slurminfo, but keep serving length-prefixed batches of commands until R is closed.
+    #[allow(dead_code)]
+    #[clap(
+        long="p-slurminfo-loop",
+        number_of_values=2,
+        value_names=&["R", "W"],
+        group("slurm-managed"),
+    )]
+    pipe_loop: Option<Vec<String>>,
+    /// With --p-slurminfo or --p-slurminfo-loop, write a timing report to STDERR once the server finishes.
+    #[allow(dead_code)]
+    #[clap(long = "pipe-timing")]
+    pipe_timing: bool,
+    /// Read a command list in pipe server format from R and print one shell command per line, for use with
+    /// GNU parallel.  All other arguments are ignored.
+    #[allow(dead_code)]
+    #[clap(long = "p-parallel", value_name = "R", group("slurm-managed"))]
+    parallel: Option<String>,
     /// Print Slurm info as a JSON string and exit.
     #[clap(long = "slurminfo", group("slurm-managed"))]
     info: bool,
+    /// Print the JSON schema of the Slurm info and exit.
+    #[cfg(feature = "schemars")]
+    #[clap(long = "slurminfo-schema", group("slurm-managed"))]
+    schema: bool,
 }
 
 #[derive(clap::Args, Debug, Clone)]
 struct NoSlurmArgs {}
 
+#[derive(clap::Args, Debug, Clone)]
+struct ToolArgs {
+    /// Write the full help to FILE and exit.  All other arguments are ignored.
+    #[allow(dead_code)]
+    #[clap(long, value_name = "FILE")]
+    dump_app_help: Option<PathBuf>,
+    /// Check a JSON parameter file for unknown, missing or mistyped fields and exit.  All other arguments are
+    /// ignored.
+    #[allow(dead_code)]
+    #[clap(long, value_name = "FILE")]
+    params_validate_file: Option<PathBuf>,
+    /// Exit without running if the outputs are up-to-date (see `Experiment::is_stale`).
+    #[clap(long)]
+    only_if_stale: bool,
+    /// Exit without running if all declared outputs exist (see `Experiment::is_complete`).
+    #[clap(long)]
+    skip_complete: bool,
+    /// Print the resolved config as JSON and exit.
+    #[clap(long)]
+    print_config: bool,
+    /// Print the filenames of the declared outputs and exit.
+    #[clap(long)]
+    list_outputs: bool,
+    /// Print the JSON hashed to produce the parameter ID, along with the resulting IDs, and exit.
+    #[clap(long)]
+    explain_id: bool,
+    /// Print the name and resolved value of each parameter and exit.
+    #[clap(long)]
+    params_keys: bool,
+    /// List the files of incomplete experiments under the root directory and exit.
+    #[clap(long)]
+    prune: bool,
+    /// Like --prune, but delete the listed files.
+    #[clap(long)]
+    prune_force: bool,
+    /// Print the commands of incomplete experiments under the root directory, in pipe server format, and exit.
+    #[clap(long)]
+    resubmit_failed: bool,
+    /// Load the experiments from index files A and B, print the output fields which differ, and exit.
+    #[clap(long, number_of_values = 2, value_names = &["A", "B"])]
+    compare_runs: Option<Vec<PathBuf>>,
+    /// Check that every experiment under the root directory can be loaded from its index file, report any which
+    /// fail and exit.
+    #[clap(long)]
+    validate_all: bool,
+    /// Write a CSV summarising every experiment under the root directory to FILE and exit.
+    #[clap(long, value_name = "FILE")]
+    summary_csv: Option<PathBuf>,
+    /// Merge the given manifest files, keeping the latest entry for each experiment, print the result and exit.
+    #[clap(long, multiple_values = true, value_name = "FILE")]
+    merge_manifests: Option<Vec<PathBuf>>,
+}
+
+impl ToolArgs {
+    /// Whether `--skip-complete` or `--only-if-stale` means the experiment should not be run.
+    fn should_skip<T: Experiment>(&self, exp: &T) -> Result<bool> {
+        Ok((self.skip_complete && exp.is_complete()) || (self.only_if_stale && !exp.is_stale()?))
+    }
+
+    /// Run the requested tools, returning `true` if the program should exit afterwards.
+    fn run<T: Experiment>(&self, exp: &T) -> Result<bool> {
+        self.run_to(exp, &mut stdout())
+    }
+
+    /// Like [`ToolArgs::run`], but writes to `w` instead of STDOUT.
+    fn run_to<T: Experiment>(&self, exp: &T, w: &mut impl Write) -> Result<bool> {
+        if self.print_config {
+            // The config isn't retained by the experiment, so it is printed by `ClArgs::into_experiment`.
+            return Ok(true);
+        }
+        if self.should_skip(exp)? {
+            eprintln!("outputs are up-to-date, skipping");
+            return Ok(true);
+        }
+        if self.list_outputs {
+            for path in exp.output_file_paths() {
+                if let Some(name) = path.file_name() {
+                    writeln!(w, "{}", name.to_string_lossy())?;
+                }
+            }
+            return Ok(true);
+        }
+        if self.explain_id {
+            let params = exp.parameter();
+            writeln!(w, "{}", serde_json::to_string(params)?)?;
+            writeln!(w, "hash: {}", id_from_serialised(params))?;
+            writeln!(w, "id: {}", params.id_str())?;
+            return Ok(true);
+        }
+        if self.params_keys {
+            match serde_json::to_value(exp.parameter())? {
+                serde_json::Value::Object(params) => {
+                    for (key, val) in params {
+                        writeln!(w, "{} = {}", key, val)?;
+                    }
+                }
+                val => writeln!(w, "{}", val)?,
+            }
+            return Ok(true);
+        }
+        if self.prune || self.prune_force {
+            for dir in sweep::prune::<T>(self.prune_force)? {
+                if self.prune_force {
+                    writeln!(w, "removed {}", dir.display())?;
+                } else {
+                    writeln!(w, "would remove {}", dir.display())?;
+                }
+            }
+            return Ok(true);
+        }
+        if self.resubmit_failed {
+            let commands = sweep::resubmit_commands::<T>()?;
+            writeln!(w, "{}", serde_json::to_string(&commands)?)?;
+            return Ok(true);
+        }
+        if let Some(paths) = &self.compare_runs {
+            let a = T::from_index_file(&paths[0])?;
+            let b = T::from_index_file(&paths[1])?;
+            for diff in diff_outputs(&a, &b)? {
+                writeln!(w, "{}", diff)?;
+            }
+            return Ok(true);
+        }
+        if self.validate_all {
+            let failed = sweep::validate_all::<T>()?;
+            for (index, e) in &failed {
+                writeln!(w, "{}: {:#}", index.display(), e)?;
+            }
+            if !failed.is_empty() {
+                anyhow::bail!("{} experiment(s) failed to load", failed.len());
+            }
+            return Ok(true);
+        }
+        if let Some(path) = &self.summary_csv {
+            let file = File::create(path).with_context(|| format!("unable to write {:?}", path))?;
+            sweep::summary_csv::<T>(std::io::BufWriter::new(file))?;
+            return Ok(true);
+        }
+        if let Some(paths) = &self.merge_manifests {
+            let merged = sweep::merge_manifests(paths)?;
+            writeln!(w, "{}", serde_json::to_string_pretty(&merged)?)?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+}
+
 // Default clap help template is:
 // {before-help}{bin} {version}
 // {author-with-newline}{about-with-newline}
@@ -451,6 +2426,10 @@ struct ClArgs<S: clap::Args, T: Experiment> {
         default_value_t
     )]
     profile: Profile,
+    /// Reject command lines containing arguments which would otherwise be silently ignored, such as parameter
+    /// arguments alongside --load-params.
+    #[clap(long)]
+    strict: bool,
     #[clap(flatten, next_help_heading = "Slurm-Managed")]
     slurm: S,
     #[clap(flatten, next_help_heading = "Input")]
@@ -467,14 +2446,64 @@ struct ClArgs<S: clap::Args, T: Experiment> {
     )]
     /// Load parameters from file.  All other parameter arguments will be ignored.
     load_params: Option<PathBuf>,
+    /// Load parameters from a previous run, given its index file.  All other parameter arguments will be ignored;
+    /// use --set to change parameters.
+    #[clap(
+        long,
+        value_name = "index file",
+        conflicts_with = "load-params",
+        help_heading = "Parameters"
+    )]
+    params_from_index: Option<PathBuf>,
+    /// Override a parameter, eg `--set epsilon=0.1`.  Nested fields are separated by `.`.  The value is parsed
+    /// as JSON, unless the field is a string.  Applied after --load-params and may be given multiple times.
+    #[clap(long = "set", value_name = "KEY=VALUE", help_heading = "Parameters")]
+    set: Vec<String>,
+    /// Total memory of the compute nodes, eg `192G`.  Used to compute memory requests as a fraction of node memory.
+    #[clap(long, value_name = "AMOUNT", help_heading = "Config")]
+    node_mem: Option<MemoryAmount>,
+    /// Record the values of these environment variables in `env.json` in the parameter directory, eg
+    /// `--export-env-file PATH,OMP_NUM_THREADS`.
+    #[clap(
+        long,
+        value_name = "VARS",
+        value_delimiter = ',',
+        help_heading = "Config"
+    )]
+    export_env_file: Vec<String>,
+    /// Prefix for Slurm job names, eg a project name.
+    #[clap(long, value_name = "PREFIX", help_heading = "Config")]
+    jobname_prefix: Option<String>,
+    /// Allow existing output files to be overwritten.
+    #[clap(long, help_heading = "Config")]
+    overwrite_outputs: bool,
+    #[clap(flatten, next_help_heading = "Tools")]
+    tools: ToolArgs,
 }
 
 /// Experiment profile.  Different profiles allow experiments to be debugged and tested easier.  
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, ArgEnum)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, ArgEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum Profile {
     Default,
     Test,
     Trace,
+    /// Reproducible benchmarking: single-threaded, no notifications and a fixed seed.
+    /// See [`ResourcePolicy::apply_profile`] and [`Profile::seed`].
+    Bench,
+}
+
+impl Profile {
+    /// The seed used by [`Profile::Bench`].
+    pub const BENCH_SEED: u64 = 0;
+
+    /// A fixed random seed, if this profile requires one.
+    pub fn seed(&self) -> Option<u64> {
+        match self {
+            Profile::Bench => Some(Profile::BENCH_SEED),
+            _ => None,
+        }
+    }
 }
 
 impl Default for Profile {
@@ -484,73 +2513,312 @@ impl Default for Profile {
 }
 
 impl<S: clap::Args, T: Experiment> ClArgs<S, T> {
+    /// Parse command-line arguments (including `argv[0]`), applying the checks of `--strict` if given.
+    fn try_parse_checked<I, A>(args: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = A>,
+        A: Into<OsString> + Clone,
+    {
+        let matches = Self::command().try_get_matches_from(args)?;
+        let parsed = Self::from_arg_matches(&matches)?;
+        if parsed.strict && (parsed.load_params.is_some() || parsed.params_from_index.is_some()) {
+            let ignored: Vec<_> = declared_args_command::<T::Parameters>()
+                .get_arguments()
+                .map(|a| a.get_id())
+                .filter(|id| matches.occurrences_of(id) > 0)
+                .map(|id| id.to_string())
+                .collect();
+            if !ignored.is_empty() {
+                anyhow::bail!(
+                    "parameter arguments are ignored when --load-params or --params-from-index is given: {}",
+                    ignored.join(", ")
+                )
+            }
+        }
+        Ok(parsed)
+    }
+
+    /// Handle `--dump-app-help` and `--params-validate-file`, which are checked before parsing since other required
+    /// arguments may be missing.  Returns `true` if the program should exit.
+    fn run_pre_parse_tools(args: &[OsString]) -> Result<bool> {
+        if let Some(path) = check_args_for_flag_value(args, "--dump-app-help")? {
+            let mut file =
+                File::create(&path).with_context(|| format!("unable to write {:?}", &path))?;
+            Self::command().write_long_help(&mut file)?;
+            return Ok(true);
+        }
+        if let Some(path) = check_args_for_flag_value(args, "--params-validate-file")? {
+            let problems = validate_params_file::<T::Parameters>(&path)?;
+            if problems.is_empty() {
+                return Ok(true);
+            }
+            for p in &problems {
+                eprintln!("{}", p);
+            }
+            anyhow::bail!("{} problem(s) found in {}", problems.len(), path);
+        }
+        Ok(false)
+    }
+
+    fn resource_overrides(&self) -> ResourceOverrides {
+        ResourceOverrides {
+            jobname_prefix: self.jobname_prefix.clone(),
+            node_mem: self.node_mem,
+        }
+    }
+
     fn into_experiment(self) -> Result<T> {
+        self.into_experiment_to(&mut stdout())
+    }
+
+    /// Like [`ClArgs::into_experiment`], but `--print-config` writes to `w` instead of STDOUT.
+    fn into_experiment_to(self, w: &mut impl Write) -> Result<T> {
         let ClArgs {
             slurm: _,
+            strict: _,
             profile,
             inputs,
             mut parameters,
             mut config,
             load_params,
+            params_from_index,
+            set,
+            node_mem: _,
+            export_env_file,
+            jobname_prefix: _,
+            overwrite_outputs: _,
+            tools,
         } = self;
+        let load_params = load_params.or_else(|| {
+            params_from_index
+                .map(|index| parameter_file_for_index(&index, SerFormat::from_path(&index)))
+        });
         if let Some(p) = load_params {
-            parameters = read_json(p).context("failed to deserialise parameters")?;
+            let format = SerFormat::from_path(&p);
+            parameters = read_file(p).with_context(|| {
+                format!(
+                    "failed to deserialise parameters as {}",
+                    format.extension().to_uppercase()
+                )
+            })?;
         }
-        T::post_parse(profile, &inputs, &mut parameters, &mut config);
-        let outputs = T::new_output(&inputs, &parameters, &config);
-        Ok(T::new(profile, config, inputs, parameters, outputs))
+        if !set.is_empty() {
+            parameters = apply_overrides(&parameters, &set)?;
+        }
+        T::try_post_parse(profile, &inputs, &mut parameters, &mut config)?;
+        if tools.print_config {
+            writeln!(w, "{}", serde_json::to_string_pretty(&config)?)?;
+        }
+        let outputs = T::try_new_output(&inputs, &parameters, &config)?;
+        let exp = T::new(profile, config, inputs, parameters, outputs);
+        if !export_env_file.is_empty() {
+            let vars: Vec<_> = export_env_file.iter().map(String::as_str).collect();
+            exp.write_env_file(&vars)?;
+        }
+        Ok(exp)
+    }
+}
+
+/// Apply `KEY=VALUE` overrides to the JSON representation of a value, see `--set`.
+fn apply_overrides<T: Serialize + DeserializeOwned>(val: &T, overrides: &[String]) -> Result<T> {
+    let mut json = serde_json::to_value(val)?;
+    for o in overrides {
+        let (key, raw) = o
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("expected KEY=VALUE, got `{}`", o))?;
+        let target = json
+            .pointer_mut(&format!("/{}", key.replace('.', "/")))
+            .ok_or_else(|| anyhow::anyhow!("unknown parameter `{}`", key))?;
+        *target = if target.is_string() {
+            serde_json::Value::String(raw.to_string())
+        } else {
+            serde_json::from_str(raw).unwrap_or_else(|_| serde_json::Value::String(raw.to_string()))
+        };
+    }
+    serde_json::from_value(json).context("invalid parameter override")
+}
+
+/// Time taken by the pipe server to compute job specifications.
+#[derive(Debug, Clone, Default)]
+struct PipeTiming {
+    per_command: Vec<Duration>,
+    total: Duration,
+}
+
+impl PipeTiming {
+    /// Write a short timing report to STDERR.
+    fn report(&self) {
+        let slowest = self.per_command.iter().max().copied().unwrap_or_default();
+        eprintln!(
+            "computed {} job specs in {:?} (slowest {:?})",
+            self.per_command.len(),
+            self.total,
+            slowest
+        );
     }
 }
 
-fn run_pipe_server<T>(commands: &str, output: &str) -> Result<()>
+/// Run the Slurm info pipe server, returning the time taken.  With `--pipe-timing`, the caller writes a report of
+/// this to STDERR.
+fn run_pipe_server<T>(commands: &str, output: &str) -> Result<PipeTiming>
 where
     T: ResourcePolicy,
 {
+    let start = Instant::now();
     let reader: File = File::open(commands)?;
     let writer: File = File::options().append(true).open(output)?;
 
     let commands: Vec<Vec<String>> = serde_json::from_reader(reader)?;
-    let mut slurm_job_specs = Vec::new();
+    let mut timing = PipeTiming::default();
+    let slurm_job_specs = compute_job_specs::<T>(commands, &mut timing)?;
+
+    serde_json::to_writer(writer, &slurm_job_specs)?;
+    timing.total = start.elapsed();
+    Ok(timing)
+}
+
+/// Run the Slurm info pipe server, serving batches of commands until the reader reaches EOF.  Each batch and
+/// response is a JSON message prefixed with its length in bytes, as a little-endian `u32`.
+fn run_pipe_server_loop<T>(commands: &str, output: &str) -> Result<PipeTiming>
+where
+    T: ResourcePolicy,
+{
+    let start = Instant::now();
+    let mut reader = BufReader::new(File::open(commands)?);
+    let mut writer: File = File::options().append(true).open(output)?;
+    let mut timing = PipeTiming::default();
+
+    loop {
+        let mut len = [0u8; 4];
+        match reader.read_exact(&mut len) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        }
+        let mut msg = vec![0u8; u32::from_le_bytes(len) as usize];
+        reader.read_exact(&mut msg)?;
+        let commands: Vec<Vec<String>> = serde_json::from_slice(&msg)?;
+
+        let response = serde_json::to_vec(&compute_job_specs::<T>(commands, &mut timing)?)?;
+        writer.write_all(&u32::try_from(response.len())?.to_le_bytes())?;
+        writer.write_all(&response)?;
+        writer.flush()?;
+    }
+
+    timing.total = start.elapsed();
+    Ok(timing)
+}
 
+fn compute_job_specs<T>(
+    commands: Vec<Vec<String>>,
+    timing: &mut PipeTiming,
+) -> Result<Vec<SlurmResources>>
+where
+    T: ResourcePolicy,
+{
+    let mut slurm_job_specs = Vec::with_capacity(commands.len());
     for cmd in commands {
+        let cmd_start = Instant::now();
         // cmd is expected to have an argv[0] which is ignored.
-        let args = ClArgs::<NoSlurmArgs, T>::try_parse_from(cmd)?;
+        let args = ClArgs::<NoSlurmArgs, T>::try_parse_checked(&cmd)?;
+        if args.strict && cmd.first().is_some_and(|a| a.starts_with('-')) {
+            anyhow::bail!("command is missing argv[0]: {:?}", cmd);
+        }
+        let overrides = args.resource_overrides();
         let exp: T = args.into_experiment()?;
-        slurm_job_specs.push(SlurmResources::new(&exp))
+        let mut spec = SlurmResources::try_with_overrides(&exp, &overrides)?;
+        spec.argv = Some(cmd.iter().skip(1).cloned().collect());
+        slurm_job_specs.push(spec);
+        timing.per_command.push(cmd_start.elapsed());
     }
+    Ok(slurm_job_specs)
+}
 
-    serde_json::to_writer(writer, &slurm_job_specs)?;
-    Ok(())
+/// Quote a string for a POSIX shell, if necessary.
+fn shell_quote(s: &str) -> String {
+    let safe = !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-./=:,+@%".contains(c));
+    if safe {
+        s.to_string()
+    } else {
+        format!("'{}'", s.replace('\'', "'\\''"))
+    }
+}
+
+/// Convert a command list in pipe server format (each command including `argv[0]`) into shell commands, one
+/// per command, suitable for GNU `parallel`.
+pub fn parallel_command_lines(commands: &[Vec<String>]) -> Vec<String> {
+    commands
+        .iter()
+        .map(|cmd| {
+            cmd.iter()
+                .map(|a| shell_quote(a))
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect()
+}
+
+/// Find the value of a flag which is handled before the other command-line arguments are parsed.
+fn check_args_for_flag_value(args: &[OsString], flag: &str) -> Result<Option<String>> {
+    let mut args = args.iter().map(|a| a.to_string_lossy().into_owned());
+    while let Some(s) = args.next() {
+        if s == flag {
+            let value = args
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("{} takes a filename argument.", flag))?;
+            return Ok(Some(value));
+        } else if s == "--help" || s == "-h" {
+            return Ok(None);
+        }
+    }
+    Ok(None)
+}
+
+/// Pipe server arguments, see [`check_args_for_slurm_pipe`]
+struct PipeArgs {
+    read: String,
+    write: String,
+    looping: bool,
+    /// Whether to report the [`PipeTiming`] (`--pipe-timing`)
+    timing: bool,
 }
 
-fn check_args_for_slurm_pipe() -> Result<Option<(String, String)>> {
-    fn parse_filename(arg: Option<String>) -> Result<String> {
-        arg.ok_or_else(|| anyhow::anyhow!("--p-slurminfo takes two filename arguments."))
+fn check_args_for_slurm_pipe(args: &[OsString]) -> Result<Option<PipeArgs>> {
+    fn parse_filename(flag: &str, arg: Option<String>) -> Result<String> {
+        arg.ok_or_else(|| anyhow::anyhow!("{} takes two filename arguments.", flag))
     }
 
-    let mut args = std::env::args();
+    let mut args = args.iter().map(|a| a.to_string_lossy().into_owned());
 
-    let mut pipe_slurminfo_found = false;
+    let mut pipe_flag = None;
     let mut rd = None;
     let mut wd = None;
+    let mut timing = false;
 
     while let Some(s) = args.next() {
-        if s == "--p-slurminfo" {
-            if pipe_slurminfo_found {
-                anyhow::bail!("--p-slurminfo supplied multiple times")
+        if s == "--p-slurminfo" || s == "--p-slurminfo-loop" {
+            if pipe_flag.is_some() {
+                anyhow::bail!("--p-slurminfo or --p-slurminfo-loop supplied multiple times")
             }
-            pipe_slurminfo_found = true;
             rd = args.next();
             wd = args.next();
+            pipe_flag = Some(s);
+        } else if s == "--pipe-timing" {
+            timing = true;
         } else if s == "--help" || s == "-h" {
             return Ok(None);
         }
     }
 
-    if pipe_slurminfo_found {
-        let rd = parse_filename(rd)?;
-        let wr = parse_filename(wd)?;
-        return Ok(Some((rd, wr)));
+    if let Some(flag) = pipe_flag {
+        return Ok(Some(PipeArgs {
+            read: parse_filename(&flag, rd)?,
+            write: parse_filename(&flag, wd)?,
+            looping: flag == "--p-slurminfo-loop",
+            timing,
+        }));
     }
     Ok(None)
 }
@@ -0,0 +1,307 @@
+//! Tools which operate on every experiment found under the root directory.
+use crate::{flatten_json, read_file, read_index, resolve_root_dir, Experiment, IdStr, Status};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt::Debug;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Parameter directories under the root directory, ie directories containing a parameter file.
+pub fn param_dirs<T: Experiment>() -> Result<Vec<PathBuf>> {
+    let root = resolve_root_dir::<T>();
+    if !root.exists() {
+        return Ok(Vec::new());
+    }
+    let mut dirs = Vec::new();
+    for entry in std::fs::read_dir(&root)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() && has_file_with_stem(entry.path(), "parameters")? {
+            dirs.push(entry.path());
+        }
+    }
+    dirs.sort();
+    Ok(dirs)
+}
+
+fn has_file_with_stem(dir: impl AsRef<Path>, stem: &str) -> Result<bool> {
+    for entry in std::fs::read_dir(dir)? {
+        if entry?.path().file_stem().is_some_and(|s| s == stem) {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Index files in a parameter directory, in any [`SerFormat`](crate::SerFormat), including those in per-input
+/// subdirectories (see [`Experiment::nested_input_dirs`]).  Symbolic links (such as the `latest-index` link, see
+/// [`Experiment::link_latest_index`]) are skipped.
+pub fn index_files(dir: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_symlink() {
+            continue;
+        }
+        if file_type.is_dir() {
+            for sub in std::fs::read_dir(entry.path())? {
+                let path = sub?.path();
+                if path.is_file() && path.file_stem().is_some_and(|f| f == "index") {
+                    files.push(path);
+                }
+            }
+            continue;
+        }
+        let path = entry.path();
+        let is_index = path
+            .file_stem()
+            .is_some_and(|f| f.to_string_lossy().ends_with("-index"));
+        if is_index {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Load every experiment under the root directory, along with the path of its index file.
+pub fn load_all<T: Experiment>() -> Result<Vec<(PathBuf, T)>> {
+    let mut exps = Vec::new();
+    for dir in param_dirs::<T>()? {
+        for index in index_files(&dir)? {
+            let exp = T::from_index_file(&index)?;
+            exps.push((index, exp));
+        }
+    }
+    Ok(exps)
+}
+
+/// Find the files of incomplete experiments (see [`Experiment::status`]): their existing outputs, index file and
+/// default Slurm logs (`INPUT_ID.err` and `INPUT_ID.out`), or their `INPUT_ID/` directory with
+/// [`Experiment::nested_input_dirs`].  Only experiments with an index file are considered: since
+/// [`Experiment::run_experiment`] writes the index file last, a parameter directory without one may belong to a job
+/// which is still running, and is left alone.  Index files which fail to load are skipped.  If `force` is `true` the
+/// files are deleted, otherwise nothing is modified.
+///
+/// Returns the files and directories of incomplete experiments.
+pub fn prune<T: Experiment>(force: bool) -> Result<Vec<PathBuf>> {
+    let mut incomplete = Vec::new();
+    for dir in param_dirs::<T>()? {
+        for index in index_files(&dir)? {
+            let exp = match T::from_index_file(&index) {
+                Ok(exp) => exp,
+                Err(e) => {
+                    eprintln!("skipping {:?}: {:#}", index, e);
+                    continue;
+                }
+            };
+            if exp.status() == Status::Complete {
+                continue;
+            }
+            if T::nested_input_dirs() {
+                if let Some(input_dir) = index.parent().filter(|d| *d != dir) {
+                    incomplete.push(input_dir.to_path_buf());
+                    continue;
+                }
+            }
+            let input_id = exp.input().id_str();
+            let logs = ["err", "out"].map(|ext| dir.join(format!("{}.{}", input_id, ext)));
+            incomplete.extend(
+                exp.output_file_paths()
+                    .into_iter()
+                    .chain(logs)
+                    .filter(|p| p.exists()),
+            );
+            incomplete.push(index);
+        }
+    }
+
+    if force {
+        let root = resolve_root_dir::<T>().canonicalize()?;
+        for path in &incomplete {
+            let parent = path.parent().map(Path::canonicalize).transpose()?;
+            if !parent.is_some_and(|p| p.starts_with(&root)) {
+                anyhow::bail!("refusing to delete {:?}: not inside {:?}", path, root);
+            }
+            if std::fs::symlink_metadata(path)?.is_dir() {
+                std::fs::remove_dir_all(path)?;
+            } else {
+                std::fs::remove_file(path)?;
+            }
+        }
+    }
+
+    Ok(incomplete)
+}
+
+/// Commands for re-running the incomplete experiments under the root directory (see [`Experiment::status`]), in the
+/// format read by the pipe server.  Commands are taken from the index files, which record the command-line arguments
+/// of the process that wrote them.  Experiments whose index files fail to load or don't record a command are skipped.
+pub fn resubmit_commands<T: Experiment>() -> Result<Vec<Vec<String>>> {
+    let mut commands = Vec::new();
+    for dir in param_dirs::<T>()? {
+        for index in index_files(&dir)? {
+            let (exp, meta) = match read_index::<T>(&index) {
+                Ok(x) => x,
+                Err(e) => {
+                    eprintln!("skipping {:?}: {:#}", index, e);
+                    continue;
+                }
+            };
+            if exp.status() == Status::Complete {
+                continue;
+            }
+            match meta.argv {
+                Some(argv) => commands.push(argv),
+                None => eprintln!("skipping {:?}: no command recorded", index),
+            }
+        }
+    }
+    Ok(commands)
+}
+
+/// An entry in a sweep manifest, describing one experiment.  A manifest is a JSON array of entries.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub param_id: String,
+    pub input_id: String,
+    /// Path of the index file
+    pub index: PathBuf,
+    /// Modification time of the index file, in seconds since the Unix epoch
+    pub written: u64,
+    pub complete: bool,
+}
+
+/// Build a manifest of every experiment under the root directory.  Index files which fail to load are skipped.
+pub fn manifest<T: Experiment>() -> Result<Vec<ManifestEntry>> {
+    let mut entries = Vec::new();
+    for dir in param_dirs::<T>()? {
+        for index in index_files(&dir)? {
+            let exp = match T::from_index_file(&index) {
+                Ok(exp) => exp,
+                Err(e) => {
+                    eprintln!("skipping {:?}: {:#}", index, e);
+                    continue;
+                }
+            };
+            let written = std::fs::metadata(&index)?
+                .modified()?
+                .duration_since(UNIX_EPOCH)?
+                .as_secs();
+            entries.push(ManifestEntry {
+                param_id: exp.parameter().id_str(),
+                input_id: exp.input().id_str(),
+                index,
+                written,
+                complete: exp.status() == Status::Complete,
+            });
+        }
+    }
+    Ok(entries)
+}
+
+/// Merge several manifest files, eg written on different head nodes.  Entries are deduplicated by parameter and
+/// input ID, keeping the most recently written; on a tie, the entry from the later file wins.
+pub fn merge_manifests<P: AsRef<Path> + Debug>(paths: &[P]) -> Result<Vec<ManifestEntry>> {
+    let mut merged: BTreeMap<(String, String), ManifestEntry> = BTreeMap::new();
+    for path in paths {
+        let entries: Vec<ManifestEntry> = read_file(path)?;
+        for entry in entries {
+            let key = (entry.param_id.clone(), entry.input_id.clone());
+            match merged.get(&key) {
+                Some(existing) if existing.written > entry.written => {}
+                _ => {
+                    merged.insert(key, entry);
+                }
+            }
+        }
+    }
+    Ok(merged.into_values().collect())
+}
+
+/// Check that every experiment under the root directory can still be reconstructed, eg after a change to the
+/// parameter or output types.  Each index file is loaded and its output re-derived with
+/// [`Experiment::try_new_output`] (using the default config).  Returns the index files which failed, with the reason.
+pub fn validate_all<T: Experiment>() -> Result<Vec<(PathBuf, anyhow::Error)>> {
+    let mut failed = Vec::new();
+    for dir in param_dirs::<T>()? {
+        for index in index_files(&dir)? {
+            let result = T::from_index_file(&index).and_then(|exp| {
+                T::try_new_output(exp.input(), exp.parameter(), &Default::default()).map(|_| ())
+            });
+            if let Err(e) = result {
+                failed.push((index, e));
+            }
+        }
+    }
+    Ok(failed)
+}
+
+/// Write a CSV with one row per experiment under the root directory, for analysis in a spreadsheet.  Columns are the
+/// parameter and input IDs, followed by the flattened fields of the parameters, inputs and outputs (prefixed with
+/// `parameters.`, `input.` and `output.`).  Sequences are written as JSON.  Index files which fail to load are skipped.
+pub fn summary_csv<T: Experiment>(mut w: impl Write) -> Result<()> {
+    let mut header = vec!["param_id".to_string(), "input_id".to_string()];
+    let mut exps = Vec::new();
+    for dir in param_dirs::<T>()? {
+        for index in index_files(&dir)? {
+            match T::from_index_file(&index) {
+                Ok(exp) => exps.push(exp),
+                Err(e) => eprintln!("skipping {:?}: {:#}", index, e),
+            }
+        }
+    }
+
+    let mut rows = Vec::new();
+    for exp in exps {
+        let mut row = serde_json::Map::new();
+        row.insert("param_id".into(), exp.parameter().id_str().into());
+        row.insert("input_id".into(), exp.input().id_str().into());
+        let parts = serde_json::json!({
+            "parameters": exp.parameter(),
+            "input": exp.input(),
+            "output": exp.output(),
+        });
+        if let serde_json::Value::Object(fields) = flatten_json(parts) {
+            row.extend(fields);
+        }
+        for key in row.keys() {
+            if !header.contains(key) {
+                header.push(key.clone());
+            }
+        }
+        rows.push(row);
+    }
+
+    let line = |fields: &[String]| {
+        fields
+            .iter()
+            .map(|f| csv_field(f))
+            .collect::<Vec<_>>()
+            .join(",")
+    };
+    writeln!(w, "{}", line(&header))?;
+    for row in rows {
+        let fields: Vec<_> = header
+            .iter()
+            .map(|k| match row.get(k) {
+                None | Some(serde_json::Value::Null) => String::new(),
+                Some(serde_json::Value::String(s)) => s.clone(),
+                Some(v) => v.to_string(),
+            })
+            .collect();
+        writeln!(w, "{}", line(&fields))?;
+    }
+    Ok(())
+}
+
+/// Quote a CSV field if necessary.
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
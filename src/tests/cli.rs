@@ -0,0 +1,371 @@
+use super::*;
+
+/// A [`TestExperiment`] with [`Experiment::protect_outputs`].
+struct Protect;
+
+impl Hooks for Protect {
+    fn protect_outputs() -> bool {
+        true
+    }
+}
+
+type Protected = Hooked<Protect>;
+
+#[test]
+fn protect_outputs_only_checked_when_running() {
+    let root = temp_root("protect-outputs");
+    with_root(&root, || {
+        TestExperiment::with(0, Params::default())
+            .write_outputs()
+            .unwrap();
+
+        assert!(Protected::try_from_args(argv(&[])).is_err());
+        assert!(Protected::try_from_args_with_slurm(argv(&[])).is_err());
+        assert!(matches!(
+            Protected::try_from_args(argv(&["--overwrite-outputs"])).unwrap(),
+            ClOutcome::Experiment(_)
+        ));
+
+        let commands = vec![argv(&[])];
+        let specs = compute_job_specs::<Protected>(commands, &mut PipeTiming::default()).unwrap();
+        assert_eq!(specs.len(), 1);
+    });
+}
+
+/// Run the tools requested in `args` on the experiment they describe, returning the output.
+fn tool_output(args: &[&str]) -> String {
+    let args = ClArgs::<NoSlurmArgs, TestExperiment>::try_parse_checked(argv(args)).unwrap();
+    let tools = args.tools.clone();
+    let mut out = Vec::new();
+    let exp = args.into_experiment_to(&mut out).unwrap();
+    assert!(tools.run_to(&exp, &mut out).unwrap());
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn list_outputs_prints_filenames() {
+    assert_eq!(
+        tool_output(&["--list-outputs", "--index", "2", "--tracelog"]),
+        "IDX002-sollog.json\nIDX002-tracelog.json\n"
+    );
+}
+
+#[test]
+fn explain_id_shows_hashed_json() {
+    let out = tool_output(&["--explain-id", "--epsilon", "0.5"]);
+    let lines: Vec<_> = out.lines().collect();
+    let params = Params {
+        epsilon: 0.5,
+        ..Default::default()
+    };
+    let json: serde_json::Value = serde_json::from_str(lines[0]).unwrap();
+    assert_eq!(json, serde_json::to_value(&params).unwrap());
+    assert_eq!(lines[1], format!("hash: {}", id_from_serialised(&params)));
+    assert_eq!(lines[2], format!("id: {}", params.id_str()));
+}
+
+#[test]
+fn comma_delimited_list() {
+    let args =
+        ClArgs::<NoSlurmArgs, TestExperiment>::try_parse_checked(argv(&["--seeds", "1,2,3"]))
+            .unwrap();
+    assert_eq!(args.parameters.seeds, vec![1, 2, 3]);
+}
+
+#[test]
+fn try_post_parse_rejects_combination() {
+    let err =
+        TestExperiment::try_from_args(argv(&["--profile", "bench", "--tracelog"])).unwrap_err();
+    assert!(err.to_string().contains("--tracelog"), "{}", err);
+    assert!(TestExperiment::try_from_args(argv(&["--profile", "bench"])).is_ok());
+}
+
+#[test]
+fn custom_parser_produces_map() {
+    let args = ClArgs::<NoSlurmArgs, TestExperiment>::try_parse_checked(argv(&[
+        "--options",
+        "presolve=off, threads=2",
+    ]))
+    .unwrap();
+    let expected: BTreeMap<_, _> = [("presolve", "off"), ("threads", "2")]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    assert_eq!(args.parameters.options, Some(expected));
+
+    assert!(
+        ClArgs::<NoSlurmArgs, TestExperiment>::try_parse_checked(argv(&["--options", "presolve"]))
+            .is_err()
+    );
+}
+
+#[derive(Debug, Clone, Args, Serialize)]
+struct Drifted {
+    #[clap(long, default_value_t = 1)]
+    cpus: u16,
+    #[clap(long)]
+    frob: bool,
+}
+
+impl Default for Drifted {
+    fn default() -> Self {
+        Drifted {
+            cpus: 1,
+            frob: true,
+        }
+    }
+}
+
+#[test]
+fn defaults_match() {
+    assert_defaults_match::<Params>();
+    let err = check_defaults_match::<Drifted>().unwrap_err().to_string();
+    assert!(err.contains("frob") && !err.contains("cpus"), "{}", err);
+}
+
+#[test]
+#[should_panic(expected = "frob")]
+fn assert_defaults_match_catches_drift() {
+    assert_defaults_match::<Drifted>();
+}
+
+#[test]
+fn try_from_args_outcomes() {
+    match TestExperiment::try_from_args(argv(&["--index", "4"])).unwrap() {
+        ClOutcome::Experiment(exp) => assert_eq!(exp.inputs.index, 4),
+        ClOutcome::Exit => panic!("expected an experiment"),
+    }
+    assert!(matches!(
+        TestExperiment::try_from_args(argv(&["--list-outputs"])).unwrap(),
+        ClOutcome::Exit
+    ));
+    assert!(TestExperiment::try_from_args(argv(&["--no-such-flag"])).is_err());
+}
+
+#[test]
+fn strict_rejects_typo() {
+    assert!(TestExperiment::try_from_args(argv(&["--strict", "--epsilonn", "0.5"])).is_err());
+    // Parameter arguments are ignored alongside --load-params, which --strict rejects
+    let root = temp_root("strict");
+    let params = root.join("parameters.json");
+    SerFormat::Json.write(&params, &Params::default()).unwrap();
+    let params = params.to_str().unwrap();
+    assert!(TestExperiment::try_from_args(argv(&["--load-params", params, "--cpus", "2"])).is_ok());
+    let err =
+        TestExperiment::try_from_args(argv(&["--strict", "--load-params", params, "--cpus", "2"]))
+            .unwrap_err();
+    assert!(err.to_string().contains("cpus"), "{}", err);
+}
+
+#[test]
+fn params_keys_lists_parameters() {
+    assert_eq!(
+        tool_output(&["--params-keys", "--cpus", "2", "--seeds", "1,2"]),
+        "epsilon = 0.0001\ncpus = 2\nparam_name = null\nseeds = [1,2]\nmem_fraction = null\noptions = null\n"
+    );
+}
+
+#[test]
+fn set_overrides_nested_field() {
+    let exp = match TestExperiment::try_from_args(argv(&[
+        "--options",
+        "presolve=off,threads=2",
+        "--set",
+        "options.presolve=on",
+        "--set",
+        "cpus=4",
+    ]))
+    .unwrap()
+    {
+        ClOutcome::Experiment(exp) => exp,
+        ClOutcome::Exit => panic!("expected an experiment"),
+    };
+    let options = exp.params.options.unwrap();
+    assert_eq!(options["presolve"], "on");
+    assert_eq!(options["threads"], "2");
+    assert_eq!(exp.params.cpus, 4);
+
+    assert!(TestExperiment::try_from_args(argv(&["--set", "nope=1"])).is_err());
+    assert!(TestExperiment::try_from_args(argv(&["--set", "cpus=many"])).is_err());
+}
+
+#[test]
+fn dump_app_help_contains_sections() {
+    let root = temp_root("dump-app-help");
+    let path = root.join("help.txt");
+    let outcome = TestExperiment::try_from_args_with_slurm(argv(&[
+        "--dump-app-help",
+        path.to_str().unwrap(),
+    ]))
+    .unwrap();
+    assert!(matches!(outcome, ClOutcome::Exit));
+    let help = std::fs::read_to_string(&path).unwrap();
+    for heading in ["Input", "Parameters", "Config", "Slurm-Managed", "Tools"] {
+        assert!(
+            help.contains(&format!("{}:", heading)),
+            "missing {}",
+            heading
+        );
+    }
+}
+
+#[test]
+fn try_new_output_fails() {
+    let err = TestExperiment::try_from_args(argv(&["--index", "1000"])).unwrap_err();
+    assert!(err.to_string().contains("no dataset"), "{}", err);
+    let commands = vec![argv(&["--index", "1000"])];
+    assert!(compute_job_specs::<TestExperiment>(commands, &mut PipeTiming::default()).is_err());
+}
+
+#[test]
+fn only_if_stale_skips_fresh_experiments() {
+    let root = temp_root("only-if-stale");
+    with_root(&root, || {
+        TestExperiment::with(0, Params::default())
+            .write_outputs()
+            .unwrap();
+        let fresh = TestExperiment::try_from_args(argv(&["--only-if-stale", "--index", "0"]));
+        assert!(matches!(fresh.unwrap(), ClOutcome::Exit));
+        let stale = TestExperiment::try_from_args(argv(&["--only-if-stale", "--index", "1"]));
+        assert!(matches!(stale.unwrap(), ClOutcome::Experiment(_)));
+    });
+}
+
+#[test]
+fn skip_complete_skips_finished_experiments() {
+    let root = temp_root("skip-complete");
+    with_root(&root, || {
+        let exp = TestExperiment::with(0, Params::default())
+            .with_input(Inputs { index: 0 }, OutputControl { trace_log: true })
+            .unwrap();
+        assert!(!exp.is_complete());
+        exp.touch_output(&exp.outputs.log).unwrap();
+        assert!(!exp.is_complete(), "partial outputs should be incomplete");
+        exp.write_outputs().unwrap();
+        assert!(exp.is_complete());
+
+        let done = TestExperiment::try_from_args(argv(&["--skip-complete", "--index", "0"]));
+        assert!(matches!(done.unwrap(), ClOutcome::Exit));
+        let todo = TestExperiment::try_from_args(argv(&["--skip-complete", "--index", "1"]));
+        assert!(matches!(todo.unwrap(), ClOutcome::Experiment(_)));
+    });
+}
+
+#[test]
+fn params_validate_file_reports_problems() {
+    let root = temp_root("params-validate");
+    let good = root.join("good.json");
+    SerFormat::Json.write(&good, &Params::default()).unwrap();
+    assert!(validate_params_file::<Params>(&good).unwrap().is_empty());
+
+    let typo = root.join("typo.json");
+    std::fs::write(
+        &typo,
+        r#"{
+  "epsilon": 0.5,
+  "cpus": 2,
+  "param_name": null,
+  "seeds": [],
+  "mem_fraction": null,
+  "options": null,
+  "epsilonn": 0.1
+}"#,
+    )
+    .unwrap();
+    assert_eq!(
+        validate_params_file::<Params>(&typo).unwrap(),
+        vec!["unknown field `epsilonn` at line 8".to_string()]
+    );
+
+    let wrong_type = root.join("wrong-type.json");
+    std::fs::write(&wrong_type, r#"{"epsilon": "big", "cpus": 1}"#).unwrap();
+    let problems = validate_params_file::<Params>(&wrong_type).unwrap();
+    assert_eq!(problems.len(), 1);
+
+    let not_json = root.join("not-json.json");
+    std::fs::write(&not_json, "{ epsilon = 0.5 }").unwrap();
+    let problems = validate_params_file::<Params>(&not_json).unwrap();
+    assert!(problems[0].starts_with("invalid JSON"), "{:?}", problems);
+
+    // The command-line tool fails when problems are found
+    assert!(TestExperiment::try_from_args(argv(&[
+        "--params-validate-file",
+        typo.to_str().unwrap()
+    ]))
+    .is_err());
+    assert!(matches!(
+        TestExperiment::try_from_args(argv(&["--params-validate-file", good.to_str().unwrap()]))
+            .unwrap(),
+        ClOutcome::Exit
+    ));
+}
+
+#[test]
+fn print_config_prints_output_control() {
+    assert_eq!(
+        tool_output(&["--print-config", "--tracelog"]),
+        "{\n  \"trace_log\": true\n}\n"
+    );
+    assert_eq!(
+        tool_output(&["--print-config"]),
+        "{\n  \"trace_log\": false\n}\n"
+    );
+}
+
+#[test]
+fn param_metadata_of_params() {
+    let meta = param_metadata::<Params>();
+    let names: Vec<_> = meta.iter().map(|m| m.name.as_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "epsilon",
+            "cpus",
+            "param_name",
+            "seeds",
+            "mem_fraction",
+            "options"
+        ]
+    );
+    let epsilon = &meta[0];
+    assert_eq!(epsilon.long.as_deref(), Some("epsilon"));
+    assert_eq!(epsilon.help.as_deref(), Some("Parameter epsilon"));
+    assert_eq!(epsilon.default, ["0.0001"]);
+    assert!(!epsilon.switch);
+    assert_eq!(meta[2].long.as_deref(), Some("param-name"));
+    assert!(meta[2].default.is_empty());
+}
+
+#[test]
+fn params_from_index_with_set_override() {
+    let root = temp_root("params-from-index");
+    let index = with_root(&root, || {
+        let exp = TestExperiment::with(
+            0,
+            Params {
+                epsilon: 0.5,
+                cpus: 4,
+                ..Default::default()
+            },
+        );
+        exp.write_parameter_file().unwrap();
+        exp.write_index_file().unwrap();
+        exp.output_dir().unwrap().join(exp.index_file_name())
+    });
+    let exp = match TestExperiment::try_from_args(argv(&[
+        "--index",
+        "1",
+        "--params-from-index",
+        index.to_str().unwrap(),
+        "--set",
+        "cpus=8",
+    ]))
+    .unwrap()
+    {
+        ClOutcome::Experiment(exp) => exp,
+        ClOutcome::Exit => panic!("expected an experiment"),
+    };
+    assert_eq!(exp.inputs.index, 1);
+    assert_eq!(exp.params.epsilon, 0.5);
+    assert_eq!(exp.params.cpus, 8);
+}
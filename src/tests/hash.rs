@@ -0,0 +1,107 @@
+use crate::hash::*;
+use crate::{impl_consistent_hash, impl_id_str_consistent_hash, IdHashAlgo, IdStr};
+
+#[derive(serde::Serialize)]
+struct HashParams {
+    epsilon: f64,
+    seeds: Vec<u64>,
+}
+
+impl_consistent_hash!(HashParams { epsilon, seeds });
+impl_id_str_consistent_hash!(HashParams);
+
+#[test]
+fn struct_with_f64_field() {
+    let a = HashParams {
+        epsilon: 0.5,
+        seeds: vec![1, 2],
+    };
+    let b = HashParams {
+        epsilon: 0.5,
+        seeds: vec![1, 2],
+    };
+    let c = HashParams {
+        epsilon: 0.25,
+        seeds: vec![1, 2],
+    };
+    assert_eq!(consistent_hash(&a), consistent_hash(&b));
+    assert_eq!(id_from_consistent_hash(&a), id_from_consistent_hash(&b));
+    assert_ne!(consistent_hash(&a), consistent_hash(&c));
+}
+
+#[test]
+fn float_zeros_and_nans() {
+    assert_eq!(consistent_hash(&0.0f64), consistent_hash(&-0.0f64));
+    assert_eq!(consistent_hash(&0.0f32), consistent_hash(&-0.0f32));
+
+    let nan_a = f64::from_bits(0x7ff8_0000_0000_0001);
+    let nan_b = f64::from_bits(0xfff8_0000_0000_0002);
+    assert!(nan_a.is_nan() && nan_b.is_nan());
+    assert_ne!(nan_a.to_bits(), nan_b.to_bits());
+    assert_eq!(consistent_hash(&nan_a), consistent_hash(&nan_b));
+    assert_eq!(
+        consistent_hash(&f32::from_bits(0x7fc0_0001)),
+        consistent_hash(&f32::NAN)
+    );
+
+    assert_ne!(consistent_hash(&0.0f64), consistent_hash(&f64::NAN));
+}
+
+#[test]
+fn id_str_from_consistent_hash() {
+    let params = HashParams {
+        epsilon: 0.5,
+        seeds: vec![1, 2],
+    };
+    assert_eq!(params.id_str(), id_from_consistent_hash(&params));
+    assert_eq!(HashParams::id_hash_algo(), IdHashAlgo::SipHash);
+}
+
+#[derive(serde::Serialize)]
+struct NamedParams {
+    name: String,
+    epsilon: f64,
+}
+
+impl_consistent_hash!(NamedParams { name, epsilon });
+impl_id_str_consistent_hash!(NamedParams, label = |p| Some(p.name.clone()));
+
+#[test]
+fn id_str_from_consistent_hash_with_label() {
+    let params = NamedParams {
+        name: "big run".into(),
+        epsilon: 0.5,
+    };
+    assert_eq!(
+        params.id_str(),
+        format!("big_run-{}", id_from_consistent_hash(&params))
+    );
+}
+
+#[derive(Hash)]
+struct DerivedHash {
+    cpus: u16,
+    name: String,
+}
+
+#[test]
+fn ids_from_derived_hash() {
+    let a = DerivedHash {
+        cpus: 4,
+        name: "a".into(),
+    };
+    let b = DerivedHash {
+        cpus: 4,
+        name: "a".into(),
+    };
+    let c = DerivedHash {
+        cpus: 8,
+        name: "a".into(),
+    };
+    let id = id_from_hash(&a);
+    assert!(!id.is_empty());
+    assert!(id.chars().all(|c| c.is_ascii_alphanumeric()), "{}", id);
+    assert_eq!(id, id_from_hash(&b));
+    assert_ne!(id, id_from_hash(&c));
+    assert_eq!(id_from_hash("abc"), id_from_hash(&String::from("abc")));
+}
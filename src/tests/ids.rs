@@ -0,0 +1,166 @@
+use super::*;
+
+#[test]
+fn id_hash_algos_are_deterministic() {
+    let params = Params::default();
+    let algos = [
+        IdHashAlgo::Sha224,
+        IdHashAlgo::Sha256Trunc,
+        IdHashAlgo::SipHash,
+    ];
+    let ids: Vec<_> = algos
+        .iter()
+        .map(|&algo| id_from_serialised_with(&params, algo))
+        .collect();
+    for (&algo, id) in algos.iter().zip(&ids) {
+        assert_eq!(&id_from_serialised_with(&params.clone(), algo), id);
+    }
+    assert_eq!(ids[0], id_from_serialised(&params));
+    assert_ne!(ids[0], ids[1]);
+    assert_ne!(ids[1], ids[2]);
+}
+
+#[test]
+fn truncated_ids_encode_truncated_digests() {
+    let params = Params::default();
+    let id = id_from_serialised_truncated(&params, 8);
+    assert!(id.len() < id_from_serialised(&params).len());
+    assert_eq!(id, id_from_serialised_truncated(&params, 8));
+    assert_eq!(
+        id_from_serialised_truncated(&params, 28),
+        id_from_serialised(&params)
+    );
+    assert_eq!(
+        id_from_serialised_truncated(&params, 100),
+        id_from_serialised(&params)
+    );
+    let other = Params {
+        epsilon: 0.5,
+        ..Default::default()
+    };
+    assert_ne!(id, id_from_serialised_truncated(&other, 8));
+}
+
+#[test]
+#[should_panic(expected = "at least one byte")]
+fn truncated_id_of_zero_bytes_panics() {
+    id_from_serialised_truncated(&Params::default(), 0);
+}
+
+#[derive(Serialize, Default)]
+struct ParamsV1 {
+    epsilon: f64,
+    cpus: u16,
+}
+
+#[derive(Serialize)]
+struct ParamsV2 {
+    epsilon: f64,
+    cpus: u16,
+    presolve: bool,
+}
+
+impl Default for ParamsV2 {
+    fn default() -> Self {
+        ParamsV2 {
+            epsilon: 0.0,
+            cpus: 0,
+            presolve: true,
+        }
+    }
+}
+
+#[test]
+fn new_defaulted_field_keeps_id() {
+    let v1 = ParamsV1 {
+        epsilon: 0.5,
+        cpus: 2,
+    };
+    let v2 = ParamsV2 {
+        epsilon: 0.5,
+        cpus: 2,
+        presolve: true,
+    };
+    assert_eq!(id_from_non_default(&v1), id_from_non_default(&v2));
+    let changed = ParamsV2 {
+        presolve: false,
+        ..v2
+    };
+    assert_ne!(id_from_non_default(&v1), id_from_non_default(&changed));
+}
+
+#[derive(Serialize)]
+struct Labelled {
+    label: String,
+    epsilon: f64,
+}
+
+impl IdStr for Labelled {
+    fn id_label(&self) -> Option<String> {
+        Some(self.label.clone())
+    }
+}
+
+#[test]
+fn id_label_prefix() {
+    let a = Labelled {
+        label: "my params".into(),
+        epsilon: 0.5,
+    };
+    let b = Labelled {
+        label: "my params".into(),
+        epsilon: 0.25,
+    };
+    assert_eq!(a.id_str(), format!("my_params-{}", id_from_serialised(&a)));
+    assert!(b.id_str().starts_with("my_params-"));
+    assert_ne!(a.id_str(), b.id_str());
+}
+
+#[derive(Serialize)]
+struct Nested {
+    name: &'static str,
+    inner: Vec<ParamsV1>,
+}
+
+#[derive(Serialize)]
+struct NestedReordered {
+    inner: Vec<ParamsV1Reordered>,
+    name: &'static str,
+}
+
+#[derive(Serialize)]
+struct ParamsV1Reordered {
+    cpus: u16,
+    epsilon: f64,
+}
+
+#[test]
+fn canonical_ids_ignore_field_order() {
+    let a = Nested {
+        name: "a",
+        inner: vec![ParamsV1 {
+            epsilon: 0.5,
+            cpus: 2,
+        }],
+    };
+    let b = NestedReordered {
+        inner: vec![ParamsV1Reordered {
+            cpus: 2,
+            epsilon: 0.5,
+        }],
+        name: "a",
+    };
+    let id = id_from_serialised_canonical(&a);
+    assert_ne!(id_from_serialised(&a), id_from_serialised(&b));
+    assert_eq!(id, id_from_serialised_canonical(&b));
+    let other = Nested { name: "b", ..a };
+    assert_ne!(id, id_from_serialised_canonical(&other));
+    // An added field changes the ID, even with a default value
+    assert_ne!(
+        id_from_serialised_canonical(&ParamsV1::default()),
+        id_from_serialised_canonical(&ParamsV2 {
+            presolve: false,
+            ..Default::default()
+        })
+    );
+}
@@ -0,0 +1,344 @@
+use super::*;
+
+#[cfg(feature = "toml")]
+#[test]
+fn toml_is_read_only() {
+    let root = temp_root("toml");
+    let path = root.join("parameters.toml");
+    assert!(SerFormat::Toml.write(&path, &Params::default()).is_err());
+    assert!(!path.exists());
+
+    std::fs::write(&path, "epsilon = 0.5\ncpus = 2\nseeds = [1, 2]\n").unwrap();
+    let params: Params = read_file(&path).unwrap();
+    assert_eq!(params.epsilon, 0.5);
+    assert_eq!(params.seeds, vec![1, 2]);
+}
+
+#[test]
+fn flatten_json_keeps_empty_objects() {
+    let nested = serde_json::json!({
+        "input": {},
+        "output": { "log": "a.json", "extra": { "trace": null } },
+        "version": 0,
+    });
+    let flat = flatten_json(nested.clone());
+    assert_eq!(
+        flat,
+        serde_json::json!({
+            "input": {},
+            "output.log": "a.json",
+            "output.extra.trace": null,
+            "version": 0,
+        })
+    );
+    assert_eq!(unflatten_json(flat).unwrap(), nested);
+}
+
+#[test]
+fn ser_formats_round_trip() {
+    let root = temp_root("ser-formats");
+    let params = Params {
+        param_name: Some("named".into()),
+        seeds: vec![1, 2, 3],
+        ..Default::default()
+    };
+    let formats = [
+        SerFormat::Json,
+        #[cfg(feature = "bincode")]
+        SerFormat::Bincode,
+        #[cfg(feature = "serde_yaml")]
+        SerFormat::Yaml,
+    ];
+    for format in formats {
+        let path = root.join(format!("parameters.{}", format.extension()));
+        assert_eq!(SerFormat::from_path(&path), format);
+        format.write(&path, &params).unwrap();
+        let read: Params = read_file(&path).unwrap();
+        assert_eq!(read, params);
+    }
+}
+
+/// A [`TestExperiment`] writing bincode index and parameter files.
+#[cfg(feature = "bincode")]
+struct Bincode;
+
+#[cfg(feature = "bincode")]
+impl Hooks for Bincode {
+    fn ser_format() -> SerFormat {
+        SerFormat::Bincode
+    }
+}
+
+#[cfg(feature = "bincode")]
+#[test]
+fn bincode_index_round_trip() {
+    let root = temp_root("bincode-index");
+    with_root(&root, || {
+        let params = Params {
+            seeds: vec![1, 2],
+            ..Default::default()
+        };
+        let inputs = Inputs { index: 3 };
+        // `Outputs` skips `trace_log` when it is none, so only an output with every field set can be read back
+        let config = OutputControl { trace_log: true };
+        let outputs = TestExperiment::new_output(&inputs, &params, &config);
+        let exp: Hooked<Bincode> =
+            TestExperiment::new(Profile::Test, config, inputs, params, outputs).into();
+        exp.write_parameter_file().unwrap();
+        exp.write_index_file().unwrap();
+
+        let path = exp.output_dir().unwrap().join(exp.index_file_name());
+        assert_eq!(path.extension().unwrap(), "bin");
+        let loaded = Hooked::<Bincode>::from_index_file(&path).unwrap();
+        assert_eq!(loaded.parameter(), exp.parameter());
+        assert_eq!(loaded.input(), exp.input());
+        assert_eq!(loaded.output(), exp.output());
+        assert_eq!(loaded.profile(), Profile::Test);
+    });
+}
+
+#[test]
+fn index_records_slurm_resources() {
+    let root = temp_root("index-slurm");
+    with_root(&root, || {
+        let exp = TestExperiment::with(0, Params::default());
+        exp.write_parameter_file().unwrap();
+        exp.write_index_file_with_slurm().unwrap();
+        let index = exp.output_dir().unwrap().join(exp.index_file_name());
+        let json: serde_json::Value = read_file(&index).unwrap();
+        assert_eq!(json["slurm"]["job-name"], exp.parameter().id_str());
+    });
+}
+
+#[test]
+fn stored_resources_match_recomputed() {
+    let root = temp_root("stored-resources");
+    with_root(&root, || {
+        let exp = TestExperiment::with(0, Params::default());
+        let index = exp.output_dir().unwrap().join(exp.index_file_name());
+        exp.write_parameter_file().unwrap();
+
+        exp.write_index_file_with_slurm().unwrap();
+        let (_, stored) = TestExperiment::from_index_file_with_slurm(&index).unwrap();
+        exp.write_index_file().unwrap();
+        let (loaded, recomputed) = TestExperiment::from_index_file_with_slurm(&index).unwrap();
+
+        assert_eq!(stored, recomputed);
+        assert_eq!(stored, SlurmResources::new(&exp).unwrap());
+        assert_eq!(loaded.params, exp.params);
+    });
+}
+
+#[test]
+fn diff_outputs_reports_one_field() {
+    let a = TestExperiment::with(0, Params::default());
+    let mut b = a.clone();
+    b.outputs.log = "other.json".into();
+    let diffs = diff_outputs(&a, &b).unwrap();
+    assert_eq!(diffs.len(), 1);
+    assert_eq!(diffs[0].key, "log");
+    assert_eq!(diffs[0].a, Some("IDX000-sollog.json".into()));
+    assert_eq!(diffs[0].b, Some("other.json".into()));
+    assert!(diff_outputs(&a, &a).unwrap().is_empty());
+}
+
+/// A [`TestExperiment`] with [`Experiment::link_latest_index`].
+#[cfg(unix)]
+mod latest {
+    use super::*;
+
+    struct Link;
+
+    impl Hooks for Link {
+        fn link_latest_index() -> bool {
+            true
+        }
+    }
+
+    type Linked = Hooked<Link>;
+
+    #[test]
+    fn latest_index_points_at_last_written() {
+        let root = temp_root("latest-index");
+        with_root(&root, || {
+            let dir = Linked::from(TestExperiment::with(0, Params::default()))
+                .output_dir()
+                .unwrap();
+            for index in [1, 0, 2] {
+                let exp = Linked::from(TestExperiment::with(index, Params::default()));
+                exp.write_parameter_file().unwrap();
+                exp.write_index_file().unwrap();
+            }
+            let link = dir.join("latest-index.json");
+            assert_eq!(
+                std::fs::read_link(&link).unwrap(),
+                PathBuf::from("IDX002-index.json")
+            );
+            // The link isn't mistaken for another experiment
+            assert_eq!(crate::sweep::index_files(&dir).unwrap().len(), 3);
+        });
+    }
+}
+
+#[test]
+fn index_keys_ignore_inner_renaming() {
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    #[serde(rename_all = "UPPERCASE")]
+    struct Renamed {
+        input: u64,
+        output: String,
+    }
+
+    let index = Index {
+        input: Renamed {
+            input: 1,
+            output: "a".into(),
+        },
+        output: Renamed {
+            input: 2,
+            output: "b".into(),
+        },
+        profile: Some(Profile::Test),
+        slurm: None,
+        argv: Some(vec!["test".into()]),
+        id_hash: Some(IdHashAlgo::Sha224),
+        version: 1,
+    };
+    let json = serde_json::to_value(&index).unwrap();
+    let keys: Vec<_> = json.as_object().unwrap().keys().cloned().collect();
+    assert_eq!(
+        keys,
+        ["input", "output", "profile", "slurm", "argv", "id_hash", "version"]
+    );
+    assert_eq!(json["input"]["INPUT"], 1);
+    assert_eq!(json["output"]["OUTPUT"], "b");
+
+    let loaded: Index<Renamed, Renamed> = serde_json::from_value(json).unwrap();
+    assert_eq!(loaded.input, index.input);
+    assert_eq!(loaded.output, index.output);
+}
+
+#[test]
+fn concurrent_writers_to_same_path() {
+    let root = temp_root("concurrent-writers");
+    let path = root.join("parameters.json");
+    let written: Vec<bool> = std::thread::scope(|s| {
+        let handles: Vec<_> = (1..=8)
+            .map(|cpus| {
+                let path = &path;
+                s.spawn(move || {
+                    let params = Params {
+                        cpus,
+                        ..Default::default()
+                    };
+                    for _ in 0..20 {
+                        SerFormat::Json.write(path, &params).unwrap();
+                    }
+                    SerFormat::Json
+                        .write_new(path.with_extension("new"), &params)
+                        .unwrap()
+                })
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+
+    // The file is intact, and from one of the writers
+    let params: Params = read_file(&path).unwrap();
+    assert!((1..=8).contains(&params.cpus));
+    // Exactly one writer creates the file with write_new
+    assert_eq!(written.iter().filter(|&&w| w).count(), 1);
+    // No temporary files are left behind
+    let names: Vec<_> = std::fs::read_dir(&root)
+        .unwrap()
+        .map(|e| e.unwrap().file_name())
+        .collect();
+    assert_eq!(names.len(), 2, "{:?}", names);
+}
+
+/// A [`TestExperiment`] at index version 2, which renamed the `sollog` output of version 1 to `log`.
+struct Migrate;
+
+impl Hooks for Migrate {
+    fn index_version() -> u32 {
+        2
+    }
+
+    fn migrate_index(value: &mut serde_json::Value, from_version: u32) {
+        if from_version < 2 {
+            let output = value["output"].as_object_mut().unwrap();
+            if let Some(log) = output.remove("sollog") {
+                output.insert("log".into(), log);
+            }
+        }
+    }
+}
+
+type Migrated = Hooked<Migrate>;
+
+#[test]
+fn migrate_v1_index() {
+    let root = temp_root("migrate-index");
+    with_root(&root, || {
+        let exp = Migrated::from(TestExperiment::with(0, Params::default()));
+        exp.write_parameter_file().unwrap();
+        let path = exp.output_dir().unwrap().join(exp.index_file_name());
+        let v1 = serde_json::json!({
+            "input": { "index": 0 },
+            "output": { "sollog": "IDX000-sollog.json" },
+            "profile": "default",
+            "version": 1,
+        });
+        SerFormat::Json.write(&path, &v1).unwrap();
+
+        let loaded = Migrated::from_index_file(&path).unwrap();
+        assert_eq!(loaded.output(), exp.output());
+        // Without the migration, the old layout can't be read
+        assert!(TestExperiment::from_index_file(&path).is_err());
+
+        // Current index files are read as they are
+        exp.write_index_file().unwrap();
+        let loaded = Migrated::from_index_file(&path).unwrap();
+        assert_eq!(loaded.output(), exp.output());
+    });
+}
+
+struct FailPostRun;
+
+impl Hooks for FailPostRun {
+    fn post_run(_: &TestExperiment) -> Result<()> {
+        anyhow::bail!("post_run failed")
+    }
+}
+
+#[test]
+fn run_experiment_indexes_finished_runs() {
+    let root = temp_root("run-experiment");
+    with_root(&root, || {
+        let index = |exp: &TestExperiment| exp.output_dir().unwrap().join(exp.index_file_name());
+
+        let exp = TestExperiment::with(0, Params::default());
+        exp.run_experiment(|e| e.write_outputs()).unwrap();
+        assert!(index(&exp).exists());
+        assert!(exp.is_complete());
+
+        let exp = TestExperiment::with(1, Params::default());
+        let err = exp
+            .run_experiment(|_| anyhow::bail!("body failed"))
+            .unwrap_err();
+        assert!(err.to_string().contains("body failed"), "{}", err);
+        assert!(!index(&exp).exists());
+
+        let exp = Hooked::<FailPostRun>::from(TestExperiment::with(2, Params::default()));
+        let mut ran = false;
+        let err = exp
+            .run_experiment(|_| {
+                ran = true;
+                Ok(())
+            })
+            .unwrap_err();
+        assert!(ran);
+        assert!(err.to_string().contains("post_run failed"), "{}", err);
+        assert!(!index(&exp.0).exists());
+    });
+}
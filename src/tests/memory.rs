@@ -0,0 +1,66 @@
+use super::*;
+
+#[test]
+fn half_of_node_memory() {
+    let node_mem = MemoryAmount::from_gib(64);
+    assert_eq!(
+        node_memory_fraction(Some(node_mem), 0.5).unwrap(),
+        MemoryAmount::from_gib(32)
+    );
+    assert!(node_memory_fraction(Some(node_mem), 0.0).is_err());
+    assert!(node_memory_fraction(Some(node_mem), 1.5).is_err());
+    assert!(node_memory_fraction(None, 0.5).is_err());
+}
+
+#[test]
+fn slurm_suffixes() {
+    assert_eq!(
+        MemoryAmount::from_str_slurm("4G").unwrap(),
+        MemoryAmount::from_gib(4)
+    );
+    assert_eq!(
+        MemoryAmount::from_str_slurm("512M").unwrap(),
+        MemoryAmount::from_mb(512)
+    );
+    assert_eq!(
+        MemoryAmount::from_str_slurm("1T").unwrap(),
+        MemoryAmount::from_gib(1024)
+    );
+    assert_eq!(
+        MemoryAmount::from_str_slurm("512").unwrap(),
+        MemoryAmount::from_mb(512)
+    );
+    assert!(MemoryAmount::from_str_slurm("4X").is_err());
+}
+
+#[test]
+fn slurm_amount_overflow_is_an_error() {
+    let err = MemoryAmount::from_str_slurm("99999999999999999T").unwrap_err();
+    assert!(err.to_string().contains("too large"), "{}", err);
+    assert!(MemoryAmount::from_str_slurm(&format!("{}G", usize::MAX)).is_err());
+}
+
+#[test]
+fn binary_units() {
+    assert_eq!(MemoryAmount::from_gib(4).as_mb(), 4096);
+    assert_eq!(MemoryAmount::from_gib(4), MemoryAmount::from_gb(4));
+    assert_eq!(MemoryAmount::from_mib(512).as_mib(), 512);
+    assert_eq!(MemoryAmount::from_gib_f64(0.5).as_mib(), 512);
+    assert_eq!(MemoryAmount::from_bytes(3 << 20).as_mib(), 3);
+}
+
+#[test]
+fn parse_unit_suffixes() {
+    let parse = |s: &str| s.parse::<MemoryAmount>().unwrap();
+    assert_eq!(parse("4GB"), MemoryAmount::from_gb(4));
+    assert_eq!(parse("4gb"), MemoryAmount::from_gb(4));
+    assert_eq!(parse(" 512 MB "), MemoryAmount::from_mb(512));
+    assert_eq!(parse("1.5GB"), MemoryAmount::from_mb(1536));
+    assert_eq!(parse("0.0001GB"), MemoryAmount::from_mb(1));
+    assert_eq!(parse("2TB"), MemoryAmount::from_gb(2048));
+    assert_eq!(parse("512"), MemoryAmount::from_mb(512));
+    assert_eq!(parse("4G"), MemoryAmount::from_gib(4));
+    for bad in ["GB", "-1GB", "NaNGB", "four GB", "4PB"] {
+        assert!(bad.parse::<MemoryAmount>().is_err(), "{:?}", bad);
+    }
+}
@@ -0,0 +1,511 @@
+//! Unit tests.  [`TestExperiment`] mirrors the experiment in `examples/usage.rs`.
+use crate::*;
+use std::marker::PhantomData;
+use std::time::Duration;
+
+mod cli;
+mod hash;
+mod ids;
+mod index;
+mod memory;
+mod paths;
+mod pipe;
+mod slurm;
+mod sweep;
+
+#[derive(Debug, Clone, Args, Serialize, Deserialize, PartialEq)]
+pub struct Inputs {
+    /// Dataset index
+    #[clap(long, default_value_t = 0)]
+    pub index: u64,
+}
+
+impl IdStr for Inputs {
+    fn id_str(&self) -> String {
+        format!("IDX{:03}", self.index)
+    }
+}
+
+#[derive(Debug, Clone, Args, Serialize, Deserialize, PartialEq)]
+pub struct Params {
+    /// Parameter epsilon
+    #[clap(long, default_value_t = 0.0001)]
+    pub epsilon: f64,
+    /// Number of threads to use
+    #[clap(long, default_value_t = 1)]
+    pub cpus: u16,
+    /// Give parameters a name (otherwise use a hash of the parameter values)
+    #[clap(long)]
+    pub param_name: Option<String>,
+    /// Random seeds, eg `--seeds 1,2,3`
+    #[clap(long, value_delimiter = ',')]
+    pub seeds: Vec<u64>,
+    /// Request this fraction of the node's memory
+    #[clap(long)]
+    pub mem_fraction: Option<f64>,
+    /// Solver options, eg `--options presolve=off,threads=2`
+    #[clap(long, parse(try_from_str = parse_key_values))]
+    pub options: Option<BTreeMap<String, String>>,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Params {
+            epsilon: 0.0001,
+            cpus: 1,
+            param_name: None,
+            seeds: Vec::new(),
+            mem_fraction: None,
+            options: None,
+        }
+    }
+}
+
+impl IdStr for Params {
+    fn id_str(&self) -> String {
+        self.param_name
+            .clone()
+            .unwrap_or_else(|| id_from_serialised(self))
+    }
+}
+
+#[derive(Debug, Clone, Args, Default, Serialize, Deserialize)]
+pub struct OutputControl {
+    /// Enable additional output
+    #[clap(long = "tracelog")]
+    pub trace_log: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Outputs {
+    pub log: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub trace_log: Option<String>,
+}
+
+/// Overrides of the [`Experiment`] and [`ResourcePolicy`] defaults.
+#[derive(Debug, Clone, Default)]
+pub struct Policy {
+    pub input_files: Vec<PathBuf>,
+    pub begin: Option<Duration>,
+    pub nodes: Option<usize>,
+    pub ntasks_per_node: Option<usize>,
+    pub mail_user: Option<String>,
+    pub constraint: Option<String>,
+    pub nodelist: Option<String>,
+    pub gpus: Option<usize>,
+    pub gpus_per_node: Option<usize>,
+    pub gpus_per_task: Option<usize>,
+    pub wckey: Option<String>,
+    pub propagate: Option<String>,
+    pub known_features: Option<Vec<String>>,
+    pub time_min: Option<Duration>,
+    pub mem_per_gpu: Option<MemoryAmount>,
+    pub mem_per_cpu: Option<MemoryAmount>,
+    pub kill_on_invalid_dep: Option<bool>,
+    pub cpu_bind: Option<String>,
+    pub prolog: Option<String>,
+    pub epilog: Option<String>,
+    pub container_image: Option<String>,
+    pub container_mounts: Option<String>,
+    pub container_workdir: Option<String>,
+    pub oversubscribe: bool,
+    pub nice: Option<i32>,
+    pub partition: Option<String>,
+    pub qos: Option<String>,
+    pub account: Option<String>,
+    pub array: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TestExperiment {
+    pub profile: Profile,
+    pub inputs: Inputs,
+    pub params: Params,
+    pub outputs: Outputs,
+    pub policy: Policy,
+}
+
+impl TestExperiment {
+    pub fn with(index: u64, params: Params) -> Self {
+        let inputs = Inputs { index };
+        let outputs = Self::new_output(&inputs, &params, &Default::default());
+        Self::new(
+            Profile::Default,
+            Default::default(),
+            inputs,
+            params,
+            outputs,
+        )
+    }
+
+    /// Create empty output files.
+    pub fn write_outputs(&self) -> Result<()> {
+        self.output_dir()?;
+        for path in self.output_file_paths() {
+            std::fs::write(path, "")?;
+        }
+        Ok(())
+    }
+}
+
+impl Experiment for TestExperiment {
+    type Parameters = Params;
+    type Config = OutputControl;
+    type Input = Inputs;
+    type Output = Outputs;
+
+    fn parameter(&self) -> &Params {
+        &self.params
+    }
+
+    fn input(&self) -> &Inputs {
+        &self.inputs
+    }
+
+    fn output(&self) -> &Outputs {
+        &self.outputs
+    }
+
+    fn profile(&self) -> Profile {
+        self.profile
+    }
+
+    fn new(
+        profile: Profile,
+        _config: OutputControl,
+        inputs: Inputs,
+        params: Params,
+        outputs: Outputs,
+    ) -> Self {
+        TestExperiment {
+            profile,
+            inputs,
+            params,
+            outputs,
+            policy: Policy::default(),
+        }
+    }
+
+    fn new_output(inputs: &Inputs, _params: &Params, config: &OutputControl) -> Outputs {
+        Outputs {
+            log: format!("{}-sollog.json", inputs.id_str()),
+            trace_log: if config.trace_log {
+                Some(format!("{}-tracelog.json", inputs.id_str()))
+            } else {
+                None
+            },
+        }
+    }
+
+    fn try_new_output(inputs: &Inputs, params: &Params, config: &OutputControl) -> Result<Outputs> {
+        if inputs.index >= 1000 {
+            anyhow::bail!("no dataset with index {}", inputs.index)
+        }
+        Ok(Self::new_output(inputs, params, config))
+    }
+
+    fn input_files(&self) -> Vec<PathBuf> {
+        self.policy.input_files.clone()
+    }
+
+    fn try_post_parse(
+        prof: Profile,
+        _inputs: &Inputs,
+        _params: &mut Params,
+        config: &mut OutputControl,
+    ) -> Result<()> {
+        if prof == Profile::Bench && config.trace_log {
+            anyhow::bail!("--tracelog would perturb benchmark timings")
+        }
+        Ok(())
+    }
+
+    fn root_dir() -> PathBuf {
+        std::env::temp_dir().join("labrat-tests")
+    }
+}
+
+impl ResourcePolicy for TestExperiment {
+    fn script(&self) -> String {
+        String::from("#!/bin/bash\n")
+    }
+
+    fn time(&self) -> Duration {
+        Duration::from_secs(300)
+    }
+
+    fn memory(&self) -> MemoryAmount {
+        MemoryAmount::from_gb(4)
+    }
+
+    fn memory_fraction(&self) -> Option<f64> {
+        self.params.mem_fraction
+    }
+
+    fn cpus(&self) -> usize {
+        self.params.cpus as usize
+    }
+
+    fn mail_user(&self) -> Option<String> {
+        self.policy.mail_user.clone()
+    }
+
+    fn constraint(&self) -> Option<String> {
+        self.policy.constraint.clone()
+    }
+
+    fn nodelist(&self) -> Option<String> {
+        self.policy.nodelist.clone()
+    }
+
+    fn gpus(&self) -> Option<usize> {
+        self.policy.gpus
+    }
+
+    fn gpus_per_node(&self) -> Option<usize> {
+        self.policy.gpus_per_node
+    }
+
+    fn gpus_per_task(&self) -> Option<usize> {
+        self.policy.gpus_per_task
+    }
+
+    fn wckey(&self) -> Option<String> {
+        self.policy.wckey.clone()
+    }
+
+    fn propagate(&self) -> Option<String> {
+        self.policy.propagate.clone()
+    }
+
+    fn known_features(&self) -> Option<Vec<String>> {
+        self.policy.known_features.clone()
+    }
+
+    fn time_min(&self) -> Option<Duration> {
+        self.policy.time_min
+    }
+
+    fn mem_per_gpu(&self) -> Option<MemoryAmount> {
+        self.policy.mem_per_gpu
+    }
+
+    fn mem_per_cpu(&self) -> Option<MemoryAmount> {
+        self.policy.mem_per_cpu
+    }
+
+    fn kill_on_invalid_dep(&self) -> Option<bool> {
+        self.policy.kill_on_invalid_dep
+    }
+
+    fn cpu_bind(&self) -> Option<String> {
+        self.policy.cpu_bind.clone()
+    }
+
+    fn prolog(&self) -> Option<String> {
+        self.policy.prolog.clone()
+    }
+
+    fn epilog(&self) -> Option<String> {
+        self.policy.epilog.clone()
+    }
+
+    fn container_image(&self) -> Option<String> {
+        self.policy.container_image.clone()
+    }
+
+    fn container_mounts(&self) -> Option<String> {
+        self.policy.container_mounts.clone()
+    }
+
+    fn container_workdir(&self) -> Option<String> {
+        self.policy.container_workdir.clone()
+    }
+
+    fn oversubscribe(&self) -> bool {
+        self.policy.oversubscribe
+    }
+
+    fn nice(&self) -> Option<i32> {
+        self.policy.nice
+    }
+
+    fn partition(&self) -> Option<String> {
+        self.policy.partition.clone()
+    }
+
+    fn qos(&self) -> Option<String> {
+        self.policy.qos.clone()
+    }
+
+    fn account(&self) -> Option<String> {
+        self.policy.account.clone()
+    }
+
+    fn array(&self) -> Option<String> {
+        self.policy.array.clone()
+    }
+
+    fn nodes(&self) -> usize {
+        self.policy.nodes.unwrap_or(1)
+    }
+
+    fn ntasks_per_node(&self) -> Option<usize> {
+        self.policy.ntasks_per_node
+    }
+
+    fn begin(&self) -> Option<Duration> {
+        self.policy.begin
+    }
+}
+
+/// Overrides of the [`Experiment`] associated functions, which can't be set through [`Policy`].  The defaults are
+/// those of [`TestExperiment`].
+pub trait Hooks {
+    fn protect_outputs() -> bool {
+        TestExperiment::protect_outputs()
+    }
+
+    fn index_version() -> u32 {
+        TestExperiment::index_version()
+    }
+
+    fn migrate_index(value: &mut serde_json::Value, from_version: u32) {
+        TestExperiment::migrate_index(value, from_version)
+    }
+
+    fn link_latest_index() -> bool {
+        TestExperiment::link_latest_index()
+    }
+
+    fn ser_format() -> SerFormat {
+        TestExperiment::ser_format()
+    }
+
+    fn verify_parameter_file() -> bool {
+        TestExperiment::verify_parameter_file()
+    }
+
+    fn nested_input_dirs() -> bool {
+        TestExperiment::nested_input_dirs()
+    }
+
+    fn post_run(exp: &TestExperiment) -> Result<()> {
+        exp.post_run()
+    }
+}
+
+/// A [`TestExperiment`] with the [`Hooks`] of `H`.
+pub struct Hooked<H>(pub TestExperiment, PhantomData<H>);
+
+impl<H> From<TestExperiment> for Hooked<H> {
+    fn from(exp: TestExperiment) -> Self {
+        Hooked(exp, PhantomData)
+    }
+}
+
+impl<H: Hooks> Experiment for Hooked<H> {
+    type Parameters = Params;
+    type Config = OutputControl;
+    type Input = Inputs;
+    type Output = Outputs;
+
+    fn parameter(&self) -> &Params {
+        self.0.parameter()
+    }
+
+    fn input(&self) -> &Inputs {
+        self.0.input()
+    }
+
+    fn output(&self) -> &Outputs {
+        self.0.output()
+    }
+
+    fn profile(&self) -> Profile {
+        self.0.profile()
+    }
+
+    fn new(
+        profile: Profile,
+        config: OutputControl,
+        inputs: Inputs,
+        params: Params,
+        outputs: Outputs,
+    ) -> Self {
+        TestExperiment::new(profile, config, inputs, params, outputs).into()
+    }
+
+    fn new_output(inputs: &Inputs, params: &Params, config: &OutputControl) -> Outputs {
+        TestExperiment::new_output(inputs, params, config)
+    }
+
+    fn root_dir() -> PathBuf {
+        TestExperiment::root_dir()
+    }
+
+    fn protect_outputs() -> bool {
+        H::protect_outputs()
+    }
+
+    fn index_version() -> u32 {
+        H::index_version()
+    }
+
+    fn migrate_index(value: &mut serde_json::Value, from_version: u32) {
+        H::migrate_index(value, from_version)
+    }
+
+    fn link_latest_index() -> bool {
+        H::link_latest_index()
+    }
+
+    fn ser_format() -> SerFormat {
+        H::ser_format()
+    }
+
+    fn verify_parameter_file() -> bool {
+        H::verify_parameter_file()
+    }
+
+    fn nested_input_dirs() -> bool {
+        H::nested_input_dirs()
+    }
+
+    fn post_run(&self) -> Result<()> {
+        H::post_run(&self.0)
+    }
+}
+
+impl<H: Hooks> ResourcePolicy for Hooked<H> {
+    fn script(&self) -> String {
+        self.0.script()
+    }
+
+    fn time(&self) -> Duration {
+        self.0.time()
+    }
+
+    fn memory(&self) -> MemoryAmount {
+        self.0.memory()
+    }
+}
+
+/// A fresh, empty directory for a test to use as the root directory.
+pub fn temp_root(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("labrat-test-{}-{}", name, std::process::id()));
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Command-line arguments, including `argv[0]`.
+pub fn argv(args: &[&str]) -> Vec<String> {
+    std::iter::once("test")
+        .chain(args.iter().copied())
+        .map(String::from)
+        .collect()
+}
@@ -0,0 +1,250 @@
+use super::*;
+
+#[test]
+fn output_file_paths_of_example() {
+    let root = temp_root("output-file-paths");
+    with_root(&root, || {
+        let params = Params::default();
+        let inputs = Inputs { index: 3 };
+        let config = OutputControl { trace_log: true };
+        let outputs = TestExperiment::new_output(&inputs, &params, &config);
+        let exp = TestExperiment::new(Profile::Default, config, inputs, params, outputs);
+        let dir = root.join(exp.parameter().id_str());
+        assert_eq!(
+            exp.output_file_paths(),
+            vec![
+                dir.join("IDX003-sollog.json"),
+                dir.join("IDX003-tracelog.json")
+            ]
+        );
+
+        let exp = TestExperiment::with(3, Params::default());
+        assert_eq!(
+            exp.output_file_paths(),
+            vec![dir.join("IDX003-sollog.json")]
+        );
+        assert!(
+            !dir.exists(),
+            "output_file_paths shouldn't create directories"
+        );
+    });
+}
+
+#[test]
+fn with_root_overrides_root_dir() {
+    let root = temp_root("with-root");
+    let exp = TestExperiment::with(0, Params::default());
+    let path = with_root(&root, || exp.get_output_path("a.txt").unwrap());
+    assert_eq!(path, root.join(exp.parameter().id_str()).join("a.txt"));
+    assert!(root.join(exp.parameter().id_str()).is_dir());
+    assert_ne!(resolve_root_dir::<TestExperiment>(), root);
+}
+
+#[test]
+fn output_dir_size_of_fixture() {
+    let root = temp_root("output-dir-size");
+    with_root(&root, || {
+        let exp = TestExperiment::with(0, Params::default());
+        let dir = exp.get_output_path("").unwrap();
+        std::fs::write(dir.join("a"), [0u8; 100]).unwrap();
+        std::fs::create_dir(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("sub").join("b"), [0u8; 23]).unwrap();
+        assert_eq!(exp.output_dir_size().unwrap(), 123);
+    });
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn touching_outputs_makes_them_fresh() {
+    use std::time::SystemTime;
+
+    let root = temp_root("touch-outputs");
+    with_root(&root, || {
+        let input = root.join("input.txt");
+        std::fs::write(&input, "").unwrap();
+        let mut exp = TestExperiment::with(0, Params::default());
+        exp.policy.input_files = vec![input];
+        assert!(exp.is_stale().unwrap());
+
+        exp.touch_all_outputs().unwrap();
+        let past = SystemTime::now() - Duration::from_secs(60);
+        for path in exp.output_file_paths() {
+            std::fs::File::options()
+                .append(true)
+                .open(path)
+                .unwrap()
+                .set_modified(past)
+                .unwrap();
+        }
+        assert!(exp.is_stale().unwrap());
+
+        exp.touch_output(&exp.outputs.log).unwrap();
+        assert!(!exp.is_stale().unwrap());
+    });
+}
+
+#[test]
+fn relative_output_path_is_under_param_id() {
+    let exp = TestExperiment::with(0, Params::default());
+    let expected = PathBuf::from(exp.parameter().id_str()).join("out.txt");
+    assert_eq!(exp.relative_output_path("out.txt"), expected);
+    assert!(exp.relative_output_path("out.txt").is_relative());
+}
+
+#[test]
+fn relative_output_path_follows_nested_input_dirs() {
+    struct Nested;
+    impl Hooks for Nested {
+        fn nested_input_dirs() -> bool {
+            true
+        }
+    }
+
+    let root = temp_root("relative-nested");
+    let exp = Hooked::<Nested>::from(TestExperiment::with(4, Params::default()));
+    let expected = PathBuf::from(exp.parameter().id_str())
+        .join(exp.input().id_str())
+        .join("out.txt");
+    assert_eq!(exp.relative_output_path("out.txt"), expected);
+    with_root(&root, || {
+        assert_eq!(
+            root.join(exp.relative_output_path("out.txt")),
+            exp.output_dir().unwrap().join("out.txt")
+        );
+    });
+}
+
+#[test]
+fn with_parameter_rederives_outputs() {
+    let exp = TestExperiment::with(0, Params::default());
+    let mut stale = exp.clone();
+    stale.outputs.log = "stale.json".into();
+    let params = Params {
+        epsilon: 0.5,
+        ..Default::default()
+    };
+    let changed = stale
+        .with_parameter(params.clone(), OutputControl::default())
+        .unwrap();
+    assert_eq!(changed.params, params);
+    assert_eq!(changed.outputs, exp.outputs);
+
+    let config = OutputControl { trace_log: true };
+    let changed = changed.with_input(Inputs { index: 7 }, config).unwrap();
+    assert_eq!(changed.outputs.log, "IDX007-sollog.json");
+    assert_eq!(
+        changed.outputs.trace_log.as_deref(),
+        Some("IDX007-tracelog.json")
+    );
+    assert_eq!(changed.params, params);
+}
+
+#[test]
+fn input_path_uses_env_override() {
+    assert_eq!(
+        TestExperiment::input_path("data/a.csv"),
+        PathBuf::from("data/a.csv")
+    );
+    // Setting the variable itself would race with other tests, so the resolver is given its value directly.
+    let root = temp_root("input-root");
+    let path =
+        resolve_input_path::<TestExperiment>(Some(root.clone().into()), Path::new("data/a.csv"));
+    assert_eq!(path, root.join("data/a.csv"));
+}
+
+#[test]
+fn env_file_records_variables() {
+    let root = temp_root("env-file");
+    with_root(&root, || {
+        let exp = TestExperiment::with(0, Params::default());
+        // Setting a variable here would race with other tests, so record one which is already set.
+        exp.write_env_file(&["PATH", "LABRAT_TEST_ENV_FILE_UNSET"])
+            .unwrap();
+        let env: serde_json::Value = read_file(exp.get_output_path("env.json").unwrap()).unwrap();
+        assert_eq!(
+            env,
+            serde_json::json!({
+                "PATH": std::env::var("PATH").unwrap(),
+                "LABRAT_TEST_ENV_FILE_UNSET": null,
+            })
+        );
+    });
+}
+
+#[test]
+fn checkpoint_dir_is_created_under_param_dir() {
+    let root = temp_root("checkpoint-dir");
+    with_root(&root, || {
+        let exp = TestExperiment::with(0, Params::default());
+        let dir = exp.checkpoint_dir().unwrap();
+        assert_eq!(dir, root.join(exp.parameter().id_str()).join("checkpoints"));
+        assert!(dir.is_dir());
+        // Calling it again is fine
+        assert_eq!(exp.checkpoint_dir().unwrap(), dir);
+    });
+}
+
+/// A [`TestExperiment`] with [`Experiment::verify_parameter_file`].
+struct Verify;
+
+impl Hooks for Verify {
+    fn verify_parameter_file() -> bool {
+        true
+    }
+}
+
+type Verified = Hooked<Verify>;
+
+#[test]
+fn param_name_collision_is_an_error() {
+    let root = temp_root("param-collision");
+    with_root(&root, || {
+        let named = |epsilon| Params {
+            epsilon,
+            param_name: Some("mine".into()),
+            ..Default::default()
+        };
+        let a = Verified::from(TestExperiment::with(0, named(0.5)));
+        let b = Verified::from(TestExperiment::with(0, named(0.25)));
+        a.write_parameter_file().unwrap();
+        // The same parameters again are fine
+        a.write_parameter_file().unwrap();
+        let err = b.write_parameter_file().unwrap_err();
+        assert!(err.to_string().contains("collision"), "{}", err);
+        // Without verification the existing file is left as it is
+        b.0.write_parameter_file().unwrap();
+    });
+}
+
+#[cfg(unix)]
+#[test]
+fn check_writable_fails_for_read_only_root() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let root = temp_root("check-writable");
+    with_root(&root, TestExperiment::check_writable).unwrap();
+
+    std::fs::set_permissions(&root, std::fs::Permissions::from_mode(0o555)).unwrap();
+    // Permissions don't apply to the superuser
+    let enforced = File::create(root.join("probe")).is_err();
+    let result = with_root(&root, TestExperiment::check_writable);
+    std::fs::set_permissions(&root, std::fs::Permissions::from_mode(0o755)).unwrap();
+    if enforced {
+        let err = result.unwrap_err();
+        assert!(err.to_string().contains("not writable"), "{}", err);
+    }
+}
+
+#[test]
+fn labrat_root_env_changes_root() {
+    let env_root = temp_root("labrat-root-env");
+    let override_root = temp_root("labrat-root-override");
+    // Other tests resolve the root concurrently, so the resolver is given the variable's value directly.
+    let from_env = || resolve_root_dir_from::<TestExperiment>(Some(env_root.clone().into()));
+    assert_eq!(from_env(), env_root);
+    assert_eq!(with_root(&override_root, from_env), override_root);
+    assert_eq!(
+        resolve_root_dir_from::<TestExperiment>(None),
+        TestExperiment::root_dir()
+    );
+}
@@ -0,0 +1,153 @@
+use super::*;
+
+fn job_specs(commands: &[&[&str]]) -> Vec<SlurmResources> {
+    let commands = commands.iter().map(|c| argv(c)).collect();
+    compute_job_specs::<TestExperiment>(commands, &mut PipeTiming::default()).unwrap()
+}
+
+#[test]
+fn jobname_prefix_applies_to_one_command() {
+    let root = temp_root("jobname-prefix");
+    with_root(&root, || {
+        let specs = job_specs(&[
+            &["--param-name", "a", "--jobname-prefix", "proj-"],
+            &["--param-name", "b"],
+        ]);
+        assert_eq!(specs[0].job_name.as_deref(), Some("proj-a"));
+        assert_eq!(specs[1].job_name.as_deref(), Some("b"));
+    });
+}
+
+#[test]
+fn node_mem_feeds_memory_fraction() {
+    let root = temp_root("node-mem");
+    with_root(&root, || {
+        let specs = job_specs(&[&["--mem-fraction", "0.5", "--node-mem", "64G"]]);
+        assert_eq!(specs[0].memory.as_deref(), Some("32768MB"));
+
+        let commands = vec![argv(&["--mem-fraction", "0.5"])];
+        let result = compute_job_specs::<TestExperiment>(commands, &mut PipeTiming::default());
+        assert!(
+            result.is_err(),
+            "node memory leaked from a previous command"
+        );
+    });
+}
+
+#[test]
+fn pipe_server_timing() {
+    let root = temp_root("pipe-timing");
+    with_root(&root, || {
+        let (r, w) = (root.join("R"), root.join("W"));
+        let commands = vec![argv(&["--index", "1"]), argv(&["--index", "2"])];
+        std::fs::write(&r, serde_json::to_vec(&commands).unwrap()).unwrap();
+        std::fs::write(&w, "").unwrap();
+        let timing =
+            run_pipe_server::<TestExperiment>(r.to_str().unwrap(), w.to_str().unwrap()).unwrap();
+        assert_eq!(timing.per_command.len(), 2);
+        assert!(timing.per_command.iter().all(|t| !t.is_zero()));
+        assert!(timing.total >= timing.per_command.iter().sum::<Duration>());
+        let specs: Vec<SlurmResources> = read_file(&w).unwrap();
+        assert_eq!(specs.len(), 2);
+    });
+}
+
+#[test]
+fn pipe_timing_flag() {
+    let args = |a: &[&str]| -> Vec<OsString> { argv(a).into_iter().map(Into::into).collect() };
+    let pipe = check_args_for_slurm_pipe(&args(&["--p-slurminfo", "R", "W"]))
+        .unwrap()
+        .unwrap();
+    assert!(!pipe.timing);
+    let pipe = check_args_for_slurm_pipe(&args(&["--pipe-timing", "--p-slurminfo-loop", "R", "W"]))
+        .unwrap()
+        .unwrap();
+    assert!(pipe.timing && pipe.looping);
+    assert_eq!((pipe.read.as_str(), pipe.write.as_str()), ("R", "W"));
+}
+
+#[test]
+fn pipe_server_loop_serves_batches() {
+    let root = temp_root("pipe-loop");
+    with_root(&root, || {
+        let (r, w) = (root.join("R"), root.join("W"));
+        let batches = [
+            vec![argv(&["--param-name", "a"])],
+            vec![argv(&["--param-name", "b"]), argv(&["--param-name", "c"])],
+        ];
+        let mut msgs = Vec::new();
+        for batch in &batches {
+            let msg = serde_json::to_vec(batch).unwrap();
+            msgs.extend((msg.len() as u32).to_le_bytes());
+            msgs.extend(msg);
+        }
+        std::fs::write(&r, msgs).unwrap();
+        std::fs::write(&w, "").unwrap();
+        run_pipe_server_loop::<TestExperiment>(r.to_str().unwrap(), w.to_str().unwrap()).unwrap();
+
+        let mut responses = std::fs::read(&w).unwrap();
+        let mut job_names = Vec::new();
+        while !responses.is_empty() {
+            let len = u32::from_le_bytes(responses[..4].try_into().unwrap()) as usize;
+            let specs: Vec<SlurmResources> =
+                serde_json::from_slice(&responses[4..4 + len]).unwrap();
+            job_names.push(
+                specs
+                    .into_iter()
+                    .map(|s| s.job_name.unwrap())
+                    .collect::<Vec<_>>(),
+            );
+            responses.drain(..4 + len);
+        }
+        assert_eq!(job_names, [vec!["a"], vec!["b", "c"]]);
+    });
+}
+
+#[test]
+fn parallel_lines_reparse() {
+    let commands = vec![
+        argv(&["--index", "1", "--param-name", "it's"]),
+        argv(&["--index", "2", "--options", "a=1,b=two words"]),
+    ];
+    let lines = parallel_command_lines(&commands);
+    assert_eq!(lines.len(), 2);
+    for (line, cmd) in lines.iter().zip(&commands) {
+        let reparsed = shell_split(line);
+        assert_eq!(&reparsed, cmd);
+        let expected = ClArgs::<NoSlurmArgs, TestExperiment>::try_parse_checked(cmd).unwrap();
+        let parsed = ClArgs::<NoSlurmArgs, TestExperiment>::try_parse_checked(reparsed).unwrap();
+        assert_eq!(parsed.inputs, expected.inputs);
+        assert_eq!(parsed.parameters, expected.parameters);
+    }
+}
+
+/// Split a command line produced by [`shell_quote`]: words are separated by spaces and may be single-quoted, with
+/// `'\''` for a literal quote.
+fn shell_split(line: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' => quoted = !quoted,
+            '\\' if !quoted => word.extend(chars.next()),
+            ' ' if !quoted => words.push(std::mem::take(&mut word)),
+            c => word.push(c),
+        }
+    }
+    words.push(word);
+    words
+}
+
+#[test]
+fn argv_is_echoed() {
+    let root = temp_root("pipe-argv");
+    with_root(&root, || {
+        let specs = job_specs(&[&["--index", "3", "--cpus", "2"]]);
+        let argv: Vec<String> = ["--index", "3", "--cpus", "2"].map(String::from).into();
+        assert_eq!(specs[0].argv, Some(argv));
+        let json = serde_json::to_value(&specs[0]).unwrap();
+        assert_eq!(json["argv"][1], "3");
+    });
+}
@@ -0,0 +1,452 @@
+use super::*;
+
+fn resources(policy: Policy) -> SlurmResources {
+    let mut exp = TestExperiment::with(0, Params::default());
+    exp.policy = policy;
+    SlurmResources::new(&exp).unwrap()
+}
+
+#[test]
+fn begin_is_relative_to_submission() {
+    let res = resources(Policy {
+        begin: Some(Duration::from_secs(3600)),
+        ..Default::default()
+    });
+    assert_eq!(res.begin.as_deref(), Some("now+3600"));
+}
+
+#[test]
+fn ntasks_is_left_to_slurm_for_multi_node_jobs() {
+    let res = resources(Policy {
+        nodes: Some(4),
+        ntasks_per_node: Some(8),
+        ..Default::default()
+    });
+    let json = serde_json::to_value(&res).unwrap();
+    assert_eq!(json["nodes"], 4);
+    assert_eq!(json["ntasks-per-node"], 8);
+    assert!(json.get("ntasks").is_none());
+}
+
+#[test]
+fn bench_profile_defaults() {
+    let mut exp = TestExperiment::with(
+        0,
+        Params {
+            cpus: 4,
+            ..Default::default()
+        },
+    );
+    exp.profile = Profile::Bench;
+    exp.policy.mail_user = Some("me@example.com".into());
+    let res = SlurmResources::new(&exp).unwrap();
+    assert_eq!(res.cpus, 1);
+    assert_eq!(res.mail_user, None);
+    assert_eq!(Profile::Bench.seed(), Some(Profile::BENCH_SEED));
+
+    // The profile survives a round trip through the index file
+    let root = temp_root("bench-profile");
+    with_root(&root, || {
+        exp.write_parameter_file().unwrap();
+        exp.write_index_file().unwrap();
+        let index = exp.output_dir().unwrap().join(exp.index_file_name());
+        let loaded = TestExperiment::from_index_file(index).unwrap();
+        assert_eq!(loaded.profile(), Profile::Bench);
+    });
+}
+
+#[test]
+fn memory_specs_are_mutually_exclusive() {
+    let mut res = resources(Policy::default());
+    assert!(res.validate().is_ok());
+    res.mem_per_cpu = Some("1024MB".into());
+    let err = res.validate().unwrap_err().to_string();
+    assert!(
+        err.contains("mem") && err.contains("mem-per-cpu"),
+        "{}",
+        err
+    );
+    res.memory = None;
+    assert!(res.validate().is_ok());
+    res.mem_per_gpu = Some("1024MB".into());
+    assert!(res.validate().is_err());
+}
+
+#[test]
+fn constraint_and_nodelist_are_distinct_keys() {
+    let res = resources(Policy {
+        constraint: Some("intel".into()),
+        nodelist: Some("node[1-4]".into()),
+        ..Default::default()
+    });
+    let json = serde_json::to_value(&res).unwrap();
+    assert_eq!(json["constraint"], "intel");
+    assert_eq!(json["nodelist"], "node[1-4]");
+}
+
+#[test]
+fn gpu_keys() {
+    let json = serde_json::to_value(resources(Policy {
+        gpus: Some(4),
+        gpus_per_node: Some(2),
+        gpus_per_task: Some(1),
+        ..Default::default()
+    }))
+    .unwrap();
+    assert_eq!(json["gpus"], 4);
+    assert_eq!(json["gpus-per-node"], 2);
+    assert_eq!(json["gpus-per-task"], 1);
+
+    let json = serde_json::to_value(resources(Policy::default())).unwrap();
+    for key in ["gpus", "gpus-per-node", "gpus-per-task"] {
+        assert!(json.get(key).is_none(), "{}", key);
+    }
+}
+
+#[test]
+fn summary_keys() {
+    let root = temp_root("summary");
+    with_root(&root, || {
+        let exp = TestExperiment::with(0, Params::default());
+        let summary = exp.summary().unwrap();
+        let keys: Vec<_> = summary.as_object().unwrap().keys().cloned().collect();
+        assert_eq!(
+            keys,
+            ["param_id", "input_id", "job_name", "cpus", "memory", "time", "complete"]
+        );
+        assert_eq!(summary["param_id"], exp.parameter().id_str());
+        assert_eq!(summary["input_id"], "IDX000");
+        assert_eq!(summary["memory"], "4096MB");
+        assert_eq!(summary["complete"], false);
+    });
+}
+
+#[cfg(feature = "schemars")]
+#[test]
+fn schema_lists_properties() {
+    let schema = serde_json::to_value(SlurmResources::json_schema()).unwrap();
+    let properties = schema["properties"].as_object().unwrap();
+    for key in ["time", "mem", "script"] {
+        assert!(properties.contains_key(key), "{}", key);
+    }
+}
+
+#[test]
+fn wckey_key() {
+    let json = serde_json::to_value(resources(Policy {
+        wckey: Some("proj".into()),
+        ..Default::default()
+    }))
+    .unwrap();
+    assert_eq!(json["wckey"], "proj");
+    let json = serde_json::to_value(resources(Policy::default())).unwrap();
+    assert!(json.get("wckey").is_none());
+}
+
+#[test]
+fn propagate_key() {
+    let json = serde_json::to_value(resources(Policy {
+        propagate: Some("STACK,CORE".into()),
+        ..Default::default()
+    }))
+    .unwrap();
+    assert_eq!(json["propagate"], "STACK,CORE");
+    let json = serde_json::to_value(resources(Policy::default())).unwrap();
+    assert!(json.get("propagate").is_none());
+}
+
+#[test]
+fn unknown_constraint_feature() {
+    let mut exp = TestExperiment::with(0, Params::default());
+    exp.policy.known_features = Some(vec!["intel".into(), "gpu".into()]);
+    exp.policy.constraint = Some("intel&(gpu|bogus)".into());
+    let err = SlurmResources::try_new(&exp).unwrap_err().to_string();
+    assert!(err.contains("bogus"), "{}", err);
+
+    exp.policy.constraint = Some("intel&gpu*2".into());
+    assert!(SlurmResources::try_new(&exp).is_ok());
+}
+
+#[test]
+fn job_names_are_sanitized() {
+    assert_eq!(sanitize_job_name("hello world"), "hello_world");
+    assert_eq!(sanitize_job_name("a-b_c.d/e"), "a-b_c.d_e");
+    let args = argv(&["--param-name", "hello world"]);
+    let specs = compute_job_specs::<TestExperiment>(vec![args], &mut PipeTiming::default());
+    assert_eq!(specs.unwrap()[0].job_name.as_deref(), Some("hello_world"));
+}
+
+#[test]
+fn time_min_formatting_and_order() {
+    let mut exp = TestExperiment::with(0, Params::default());
+    exp.policy.time_min = Some(Duration::from_secs(120));
+    let res = SlurmResources::try_new(&exp).unwrap();
+    assert_eq!(res.time, "0-0:05:00");
+    assert_eq!(res.time_min.as_deref(), Some("0-0:02:00"));
+
+    exp.policy.time_min = Some(Duration::from_secs(600));
+    let err = SlurmResources::try_new(&exp).unwrap_err().to_string();
+    assert!(err.contains("exceeds"), "{}", err);
+}
+
+#[test]
+fn mem_per_gpu_replaces_mem() {
+    let mut exp = TestExperiment::with(0, Params::default());
+    exp.policy.mem_per_gpu = Some(MemoryAmount::from_gib(8));
+    let res = SlurmResources::try_new(&exp).unwrap();
+    assert_eq!(res.memory, None);
+    assert_eq!(res.mem_per_gpu.as_deref(), Some("8192MB"));
+
+    exp.policy.mem_per_cpu = Some(MemoryAmount::from_gib(2));
+    assert!(SlurmResources::try_new(&exp).is_err());
+}
+
+#[test]
+fn all_individual_mail_types() {
+    let types: std::collections::HashSet<_> = MailType::all_individual().into_iter().collect();
+    let expected = [
+        MailType::Begin,
+        MailType::End,
+        MailType::Fail,
+        MailType::Requeue,
+    ];
+    assert_eq!(types, expected.into_iter().collect());
+    assert_eq!(MailType::all_individual().len(), 4);
+}
+
+#[test]
+fn kill_on_invalid_dep_values() {
+    for (kill, expected) in [(true, "yes"), (false, "no")] {
+        let json = serde_json::to_value(resources(Policy {
+            kill_on_invalid_dep: Some(kill),
+            ..Default::default()
+        }))
+        .unwrap();
+        assert_eq!(json["kill-on-invalid-dep"], expected);
+    }
+    let json = serde_json::to_value(resources(Policy::default())).unwrap();
+    assert!(json.get("kill-on-invalid-dep").is_none());
+}
+
+#[test]
+fn for_all_matches_individual() {
+    let exps = [
+        TestExperiment::with(0, Params::default()),
+        TestExperiment::with(
+            1,
+            Params {
+                cpus: 8,
+                ..Default::default()
+            },
+        ),
+    ];
+    let all = SlurmResources::for_all(&exps).unwrap();
+    assert_eq!(all.len(), 2);
+    assert_eq!(all[0].cpus, 1);
+    assert_eq!(all[1].cpus, 8);
+    for (exp, res) in exps.iter().zip(&all) {
+        assert_eq!(
+            serde_json::to_value(res).unwrap(),
+            serde_json::to_value(SlurmResources::new(exp).unwrap()).unwrap()
+        );
+    }
+
+    let mut bad = exps[1].clone();
+    bad.policy.constraint = Some("bogus".into());
+    bad.policy.known_features = Some(vec!["haswell".into()]);
+    assert!(SlurmResources::for_all(&[exps[0].clone(), bad]).is_err());
+}
+
+#[test]
+fn cpu_bind_serialization() {
+    let json = serde_json::to_value(&resources(Policy::default())).unwrap();
+    assert!(json.get("cpu-bind").is_none());
+    let res = resources(Policy {
+        cpu_bind: Some("cores".into()),
+        ..Default::default()
+    });
+    let json = serde_json::to_value(&res).unwrap();
+    assert_eq!(json["cpu-bind"], "cores");
+}
+
+#[test]
+fn slurm_time_formatting() {
+    assert_eq!(fmt_as_slurm_time(0), "0-0:01:00");
+    assert_eq!(fmt_as_slurm_time(59), "0-0:01:00");
+    assert_eq!(fmt_as_slurm_time(3661), "0-1:01:01");
+    let multi_day = 2 * 86400 + 3 * 3600 + 4 * 60 + 5;
+    assert_eq!(fmt_as_slurm_time(multi_day), "2-3:04:05");
+    assert_eq!(
+        parse_slurm_time(&fmt_as_slurm_time(multi_day)).unwrap(),
+        Duration::from_secs(multi_day)
+    );
+}
+
+#[test]
+fn slurm_time_parsing() {
+    let parse = |s| parse_slurm_time(s).unwrap().as_secs();
+    assert_eq!(parse("90"), 90 * 60);
+    assert_eq!(parse("5:30"), 5 * 60 + 30);
+    assert_eq!(parse("2:03:04"), 2 * 3600 + 3 * 60 + 4);
+    assert_eq!(parse("1-2"), 86400 + 2 * 3600);
+    assert_eq!(parse("1-2:03"), 86400 + 2 * 3600 + 3 * 60);
+    assert_eq!(parse("1-2:03:04"), 86400 + 2 * 3600 + 3 * 60 + 4);
+    assert_eq!(parse(" 2h30m "), 2 * 3600 + 30 * 60);
+    for bad in ["", "1:2:3:4", "1-2:3:4:5", "-5", "1-", "soon"] {
+        assert!(parse_slurm_time(bad).is_err(), "{:?}", bad);
+    }
+}
+
+#[test]
+fn non_default_profiles_request_one_cpu() {
+    let mut exp = TestExperiment::with(
+        0,
+        Params {
+            cpus: 4,
+            ..Default::default()
+        },
+    );
+    exp.policy.mail_user = Some("me@example.com".into());
+    assert_eq!(SlurmResources::new(&exp).unwrap().cpus, 4);
+    exp.profile = Profile::Test;
+    let res = SlurmResources::new(&exp).unwrap();
+    assert_eq!(res.cpus, 1);
+    // Only the bench profile disables notifications
+    assert_eq!(res.mail_user.as_deref(), Some("me@example.com"));
+}
+
+#[test]
+fn prolog_and_epilog_wrap_script() {
+    let res = resources(Policy {
+        prolog: Some("module load gurobi".into()),
+        epilog: Some("rm -rf $TMPDIR/scratch\n".into()),
+        ..Default::default()
+    });
+    assert_eq!(
+        res.script,
+        "#!/bin/bash\nmodule load gurobi\nrm -rf $TMPDIR/scratch\n"
+    );
+    assert_eq!(resources(Policy::default()).script, "#!/bin/bash\n");
+    assert_eq!(
+        wrap_script("echo hi".into(), Some("set -e".into()), None),
+        "set -e\necho hi\n"
+    );
+}
+
+#[test]
+fn slurm_time_constructors() {
+    let t = SlurmTime::days(1)
+        .plus_hours(2)
+        .plus_minutes(3)
+        .plus_seconds(4);
+    assert_eq!(t.as_secs(), 86400 + 2 * 3600 + 3 * 60 + 4);
+    assert_eq!(t.to_string(), "1-2:03:04");
+    assert_eq!(SlurmTime::hours(2), SlurmTime::minutes(120));
+    assert_eq!(SlurmTime::minutes(1), SlurmTime::seconds(60));
+    assert_eq!(SlurmTime::seconds(30).to_string(), "0-0:01:00");
+    assert_eq!(
+        Duration::from(SlurmTime::hours(2).plus_minutes(30)),
+        Duration::from_secs(9000)
+    );
+}
+
+#[test]
+fn container_keys_serialization() {
+    let json = serde_json::to_value(&resources(Policy::default())).unwrap();
+    for key in ["container-image", "container-mounts", "container-workdir"] {
+        assert!(json.get(key).is_none(), "{}", key);
+    }
+    let res = resources(Policy {
+        container_image: Some("nvcr.io#nvidia/pytorch:23.10-py3".into()),
+        container_mounts: Some("/data:/data".into()),
+        container_workdir: Some("/work".into()),
+        ..Default::default()
+    });
+    let json = serde_json::to_value(&res).unwrap();
+    assert_eq!(json["container-image"], "nvcr.io#nvidia/pytorch:23.10-py3");
+    assert_eq!(json["container-mounts"], "/data:/data");
+    assert_eq!(json["container-workdir"], "/work");
+}
+
+#[test]
+fn oversubscribe_only_when_enabled() {
+    let json = serde_json::to_value(&resources(Policy::default())).unwrap();
+    assert!(json.get("oversubscribe").is_none());
+    let res = resources(Policy {
+        oversubscribe: true,
+        ..Default::default()
+    });
+    let json = serde_json::to_value(&res).unwrap();
+    assert_eq!(json["oversubscribe"], true);
+}
+
+#[test]
+fn negative_nice_serialization() {
+    let json = serde_json::to_value(&resources(Policy::default())).unwrap();
+    assert!(json.get("nice").is_none());
+    let res = resources(Policy {
+        nice: Some(-10),
+        ..Default::default()
+    });
+    let json = serde_json::to_value(&res).unwrap();
+    assert_eq!(json["nice"], -10);
+}
+
+#[test]
+fn partition_is_passed_to_slurm() {
+    let json = serde_json::to_value(&resources(Policy::default())).unwrap();
+    assert!(json.get("partition").is_none());
+    let res = resources(Policy {
+        partition: Some("gpu".into()),
+        ..Default::default()
+    });
+    assert_eq!(res.partition.as_deref(), Some("gpu"));
+    let json = serde_json::to_value(&res).unwrap();
+    assert_eq!(json["partition"], "gpu");
+}
+
+#[test]
+fn qos_is_passed_to_slurm() {
+    let json = serde_json::to_value(&resources(Policy::default())).unwrap();
+    assert!(json.get("qos").is_none());
+    let res = resources(Policy {
+        qos: Some("high".into()),
+        ..Default::default()
+    });
+    assert_eq!(res.qos.as_deref(), Some("high"));
+    let json = serde_json::to_value(&res).unwrap();
+    assert_eq!(json["qos"], "high");
+}
+
+#[test]
+fn account_is_passed_to_slurm() {
+    let json = serde_json::to_value(&resources(Policy::default())).unwrap();
+    assert!(json.get("account").is_none());
+    let res = resources(Policy {
+        account: Some("proj-123".into()),
+        ..Default::default()
+    });
+    assert_eq!(res.account.as_deref(), Some("proj-123"));
+    let json = serde_json::to_value(&res).unwrap();
+    assert_eq!(json["account"], "proj-123");
+}
+
+#[test]
+fn array_jobs_log_per_task() {
+    let root = temp_root("array-logs");
+    with_root(&root, || {
+        let file_name = |p: &Path| p.file_name().unwrap().to_str().unwrap().to_string();
+        let plain = resources(Policy::default());
+        assert!(plain.array.is_none());
+        assert_eq!(file_name(&plain.log_out), "IDX000.out");
+
+        let res = resources(Policy {
+            array: Some("0-99%10".into()),
+            ..Default::default()
+        });
+        let json = serde_json::to_value(&res).unwrap();
+        assert_eq!(json["array"], "0-99%10");
+        assert_eq!(file_name(&res.log_out), "IDX000-%A_%a.out");
+        assert_eq!(file_name(res.log_err.as_ref().unwrap()), "IDX000-%A_%a.err");
+    });
+}
@@ -0,0 +1,169 @@
+use super::*;
+use crate::sweep;
+
+#[test]
+fn prune_removes_only_incomplete_experiments() {
+    let root = temp_root("prune");
+    with_root(&root, || {
+        let complete = TestExperiment::with(0, Params::default());
+        complete.run_experiment(|e| e.write_outputs()).unwrap();
+
+        // Incomplete, sharing a parameter directory with the complete experiment
+        let incomplete = TestExperiment::with(1, Params::default());
+        incomplete.write_parameter_file().unwrap();
+        incomplete.write_index_file().unwrap();
+        let err_log = incomplete.log_err().unwrap();
+        std::fs::write(&err_log, "").unwrap();
+
+        // No index file: may still be running
+        let running = TestExperiment::with(
+            0,
+            Params {
+                epsilon: 0.5,
+                ..Default::default()
+            },
+        );
+        running.write_parameter_file().unwrap();
+        let running_dir = running.get_output_path("").unwrap();
+
+        let index = incomplete
+            .output_dir()
+            .unwrap()
+            .join(incomplete.index_file_name());
+        let listed = sweep::prune::<TestExperiment>(false).unwrap();
+        assert_eq!(listed, vec![err_log.clone(), index.clone()]);
+        assert!(index.exists());
+
+        sweep::prune::<TestExperiment>(true).unwrap();
+        assert!(!index.exists());
+        assert!(!err_log.exists());
+        assert!(complete.is_complete());
+        assert!(running_dir.exists());
+        assert!(sweep::prune::<TestExperiment>(false).unwrap().is_empty());
+    });
+}
+
+#[test]
+fn resubmit_incomplete_experiments() {
+    let root = temp_root("resubmit");
+    with_root(&root, || {
+        let complete = TestExperiment::with(0, Params::default());
+        complete.run_experiment(|e| e.write_outputs()).unwrap();
+        let incomplete = TestExperiment::with(1, Params::default());
+        incomplete.write_parameter_file().unwrap();
+        incomplete.write_index_file().unwrap();
+
+        // Index files record the command line of the process which wrote them
+        let argv: Vec<String> = std::env::args().collect();
+        assert_eq!(
+            sweep::resubmit_commands::<TestExperiment>().unwrap(),
+            vec![argv]
+        );
+    });
+}
+
+#[test]
+fn merge_overlapping_manifests() {
+    let root = temp_root("merge-manifests");
+    let entry = |input_id: &str, written: u64, complete: bool| sweep::ManifestEntry {
+        param_id: "P".into(),
+        input_id: input_id.into(),
+        index: root.join(format!("{}-index.json", input_id)),
+        written,
+        complete,
+    };
+    let a = root.join("a.json");
+    let b = root.join("b.json");
+    SerFormat::Json
+        .write(
+            &a,
+            &vec![entry("IDX000", 10, false), entry("IDX001", 30, true)],
+        )
+        .unwrap();
+    SerFormat::Json
+        .write(
+            &b,
+            &vec![
+                entry("IDX000", 20, true),
+                entry("IDX001", 5, false),
+                entry("IDX002", 1, false),
+            ],
+        )
+        .unwrap();
+
+    let merged = sweep::merge_manifests(&[&a, &b]).unwrap();
+    let summary: Vec<_> = merged
+        .iter()
+        .map(|e| (e.input_id.as_str(), e.written, e.complete))
+        .collect();
+    assert_eq!(
+        summary,
+        [
+            ("IDX000", 20, true),
+            ("IDX001", 30, true),
+            ("IDX002", 1, false)
+        ]
+    );
+}
+
+#[test]
+fn validate_all_reports_broken_entry() {
+    let root = temp_root("validate-all");
+    with_root(&root, || {
+        for index in [0, 1, 1000] {
+            let exp = TestExperiment::with(index, Params::default());
+            exp.write_parameter_file().unwrap();
+            exp.write_index_file().unwrap();
+        }
+        let failed = sweep::validate_all::<TestExperiment>().unwrap();
+        assert_eq!(failed.len(), 1);
+        let (index, err) = &failed[0];
+        assert_eq!(index.file_name().unwrap(), "IDX1000-index.json");
+        assert!(err.to_string().contains("no dataset"), "{}", err);
+    });
+}
+
+#[test]
+fn summary_csv_has_header_and_row() {
+    let root = temp_root("summary-csv");
+    with_root(&root, || {
+        let exp = TestExperiment::with(
+            0,
+            Params {
+                seeds: vec![1, 2],
+                ..Default::default()
+            },
+        );
+        exp.run_experiment(|e| e.write_outputs()).unwrap();
+        let mut out = Vec::new();
+        sweep::summary_csv::<TestExperiment>(&mut out).unwrap();
+        let csv = String::from_utf8(out).unwrap();
+        let lines: Vec<_> = csv.lines().collect();
+        assert_eq!(
+            lines,
+            [
+                "param_id,input_id,parameters.epsilon,parameters.cpus,parameters.param_name,parameters.seeds,\
+                 parameters.mem_fraction,parameters.options,input.index,output.log",
+                format!(
+                    "{},IDX000,0.0001,1,,\"[1,2]\",,,0,IDX000-sollog.json",
+                    exp.parameter().id_str()
+                )
+                .as_str(),
+            ]
+        );
+    });
+}
+
+#[test]
+fn summary_csv_skips_broken_index() {
+    let root = temp_root("summary-csv-broken");
+    with_root(&root, || {
+        let exp = TestExperiment::with(0, Params::default());
+        exp.run_experiment(|e| e.write_outputs()).unwrap();
+        let broken = exp.output_dir().unwrap().join("IDX001-index.json");
+        std::fs::write(&broken, "not json").unwrap();
+        let mut out = Vec::new();
+        sweep::summary_csv::<TestExperiment>(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap().lines().count(), 2);
+    });
+}